@@ -1,50 +1,157 @@
-use super::{ Blokka, State, Render, TableHeaderItem, SelectableList, Index, selectable_table, get_percentage_width };
-use mpd_client::{ Client, commands, commands::responses::SongInQueue };
+use super::{ IndexedBlock, BlockTrait, MainBlock, TrackKind, State, Render, TableHeaderItem, Index, Cells, selectable_table, get_percentage_width };
+use mpd_client::{ Client, commands, commands::responses::Song, filter::Filter, tag::Tag };
 use tui::{ backend::Backend, layout::Rect, Frame };
+use crate::client::{ StrofaClient, Progress, album_artist, year, release_date, language };
+use crate::collate;
+use crate::chunk::{ BlockKind, Chunks, Step };
+use crate::history::{ View, Location };
+use crate::index::LibraryIndex;
+use std::sync::Arc;
+use crate::command::Command;
+use async_trait::async_trait;
+use anyhow::Result;
+
+pub struct Album {
+    pub name: String,
+    pub artist: String,
+    pub year: Option<String>,
+    pub date: Option<String>,
+    pub language: Option<String>,
+    // in disc and track order
+    pub uris: Vec<String>,
+}
 
 pub struct Albums {
-    pub index: Index,
-    // pub albums: Vec<Song>,
+    pub kind: AlbumKind,
+    pub albums: Vec<Album>,
+    pub cells: Cells,
 }
 
+#[derive(Clone, PartialEq, Debug)]
 pub enum AlbumKind {
     Artist(String),
     All,
 }
 
-impl SelectableList for Albums {
-    fn index(&mut self) -> &mut Index {
-        &mut self.index
+impl Albums {
+    async fn fetch(kind: &AlbumKind, client: &Client, library: &Arc<LibraryIndex>, progress: &Progress<'_>) -> Result<Vec<Album>> {
+        let has_album = |song: &Song| !song.album().unwrap_or_default().is_empty();
+        let songs = match kind {
+            AlbumKind::Artist(artist) => client.command(commands::Find::new(Filter::tag(Tag::AlbumArtist, artist.to_string()))).await?,
+            AlbumKind::All if library.is_loaded() => library.songs().filter(|song| has_album(song)).cloned().collect(),
+            AlbumKind::All => {
+                let mut songs = client.list_library(progress).await?;
+                songs.retain(has_album);
+                songs
+            },
+        };
+
+        Ok(Albums::group(songs))
+    }
+
+    // mpd has no notion of an album, so songs sharing an album and album artist make one
+    pub fn group(mut songs: Vec<Song>) -> Vec<Album> {
+        songs.sort_by_cached_key(|song| {
+            (collate::album_artist_key(song), year(song).map(str::to_string), collate::album_key(song), song.number())
+        });
+
+        let mut albums: Vec<Album> = Vec::new();
+        for song in songs {
+            let name = song.album().unwrap_or_default();
+            let artist = album_artist(&song);
+
+            match albums.last_mut() {
+                Some(album) if album.name == name && album.artist == artist => album.uris.push(song.url),
+                _ => albums.push(Album {
+                    name: name.to_string(),
+                    artist: artist.to_string(),
+                    year: year(&song).map(str::to_string),
+                    date: release_date(&song).map(str::to_string),
+                    language: language(&song).map(str::to_string),
+                    uris: vec![song.url],
+                }),
+            }
+        }
+
+        albums
     }
 }
 
-impl Albums {
-    pub async fn new(kind: AlbumKind) -> Self {
-        //use kind to populate tracks
+impl IndexedBlock<Albums> {
+    pub async fn new(kind: AlbumKind, client: &Client, library: &Arc<LibraryIndex>, progress: &Progress<'_>) -> Result<Self> {
+        let albums = Albums::fetch(&kind, client, library, progress).await?;
+
+        Ok(Self {
+            index: Index::new(albums.len()),
+            inner: Albums { kind, albums, cells: Cells::default() },
+        })
+    }
 
-        Self {
-            index: Index::new(50),
+    pub fn update(&mut self, fresh: Self) {
+        let selected = self.selected().map(|album| (album.name.clone(), album.artist.clone()));
+        self.inner.albums = fresh.inner.albums;
+        self.inner.cells.clear();
+        self.index.anchor(self.inner.albums.len(), self.inner.albums.iter().position(|album| selected.as_ref().is_some_and(|(name, artist)| album.name == *name && album.artist == *artist)));
+    }
+
+    pub fn selected(&self) -> Option<&Album> {
+        self.inner.albums.get(self.index.inner)
+    }
+}
+
+#[async_trait]
+impl BlockTrait for IndexedBlock<Albums> {
+    async fn active_event(state: &mut State, cmd: Command) -> Result<()> {
+        let album = match &state.chunks.centre.inner.main {
+            MainBlock::Albums(albums) => albums.selected().map(|album| (album.artist.clone(), album.name.clone())),
+            _ => None,
+        };
+
+        match (cmd, album) {
+            (Command::Select, Some((artist, name))) => Chunks::load(state, Location::new(View::Tracks(TrackKind::Album(artist, name))), Step::Visit),
+
+            _ => {}
         }
+
+        Ok(())
     }
+
+    async fn hovered_event(_state: &mut State, _cmd: Command) -> Result<()> { Ok(()) }
 }
 
-impl<B: Backend> Render<B> for Albums {
-    fn render(&self, f: &mut Frame<B>, state: &State<B>, layout_chunk: Rect) {
+impl<B: Backend + Send> Render<B> for IndexedBlock<Albums> {
+    fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
         let highlight_state = (
-            state.blocks.is_active(Blokka::Main),
-            state.blocks.is_hovered(Blokka::Main)
+            state.chunks.is_active(BlockKind::Centre),
+            state.chunks.is_hovered(BlockKind::Centre)
         );
 
-        // let items: Vec<ListItem> = Vec::new(); 
-
-        // selectable_list(
-        //     f,
-        //     state,
-        //     layout_chunk,
-        //     " Albums ",
-        //     items,
-        //     highlight_state,
-        //     Some(self.index.inner)
-        // );
+        let format = |album: &Album| vec![
+            album.year.clone().unwrap_or_default(),
+            album.name.clone(),
+            album.artist.clone(),
+            album.uris.len().to_string(),
+        ];
+
+        let header = vec![
+            TableHeaderItem { text: "Year", width: 5 },
+            TableHeaderItem { text: "Album", width: get_percentage_width(layout_chunk.width, 2.0 / 5.0) },
+            TableHeaderItem { text: "Artist", width: get_percentage_width(layout_chunk.width, 2.0 / 5.0).saturating_sub(5) },
+            TableHeaderItem { text: "Tracks", width: get_percentage_width(layout_chunk.width, 1.0 / 5.0) },
+        ];
+
+        selectable_table(
+            f,
+            state,
+            layout_chunk,
+            &state.chunks.history.breadcrumb(&View::Albums(self.inner.kind.clone())),
+            &header,
+            &self.inner.albums,
+            &self.inner.cells,
+            format,
+            &self.index,
+            None,
+            highlight_state,
+        )
     }
-}
\ No newline at end of file
+}
@@ -1,43 +1,120 @@
-use super::{ Blokka, State, Render, TableHeaderItem, SelectableList, Index, selectable_table, get_percentage_width };
-use mpd_client::{ Client, commands, commands::responses::SongInQueue };
-use tui::{ backend::Backend, layout::Rect, Frame };
+use super::{ IndexedBlock, BlockTrait, MainBlock, AlbumKind, State, Render, Index, selectable_list };
+use mpd_client::{ Client, commands, commands::responses::Song, filter::Filter, tag::Tag };
+use tui::{ backend::Backend, layout::Rect, text::Span, widgets::ListItem, Frame };
+use crate::client::year;
+use crate::collate::{ self, Collated };
+use crate::chunk::{ BlockKind, Chunks, Step };
+use crate::history::{ View, Location };
+use crate::command::Command;
+use crate::index::LibraryIndex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use async_trait::async_trait;
+use anyhow::Result;
 
 pub struct Artists {
-    pub index: Index,
-    // pub artists: Vec<Artist>,
+    pub artists: Vec<String>,
 }
 
-impl SelectableList for Artists {
-    fn index(&mut self) -> &mut Index {
-        &mut self.index
+impl Artists {
+    // ordered by their sort tags, which only the index has; mpd lists the names alone
+    async fn fetch(client: &Client, library: &Arc<LibraryIndex>) -> Result<Vec<String>> {
+        let mut artists: Vec<(String, Collated)> = match library.is_loaded() {
+            true => {
+                // the first song tagged with an artist decides where it goes
+                let mut keys: HashMap<&str, Collated> = HashMap::new();
+                for song in library.songs() {
+                    for (artist, key) in collate::album_artist_keys(song) {
+                        keys.entry(artist).or_insert(key);
+                    }
+                }
+
+                keys.into_iter().map(|(artist, key)| (artist.to_string(), key)).collect()
+            },
+
+            false => client.command(commands::List::new(Tag::AlbumArtist)).await?
+                .fields
+                .into_iter()
+                .map(|(_, artist)| {
+                    let key = collate::key(&artist);
+                    (artist, key)
+                })
+                .collect(),
+        };
+
+        artists.retain(|(artist, _)| !artist.is_empty());
+        // names sharing a sort name still need a fixed order
+        artists.sort_by(|(a, a_key), (b, b_key)| (a_key, a).cmp(&(b_key, b)));
+        Ok(artists.into_iter().map(|(artist, _)| artist).collect())
+    }
+
+    // every song by the artist, in the order their albums are listed
+    pub async fn songs(client: &Client, artist: &str) -> Result<Vec<Song>> {
+        let filter = Filter::tag(Tag::AlbumArtist, artist.to_string());
+        let mut songs = client.command(commands::Find::new(filter)).await?;
+        songs.sort_by_cached_key(|song| (year(song).map(str::to_string), collate::album_key(song), song.number()));
+
+        Ok(songs)
     }
 }
 
-impl Artists {
-    pub async fn new() -> Self {
-        Self {
-            index: Index::new(50),
+impl IndexedBlock<Artists> {
+    pub async fn new(client: &Client, library: &Arc<LibraryIndex>) -> Result<Self> {
+        let artists = Artists::fetch(client, library).await?;
+
+        Ok(Self {
+            index: Index::new(artists.len()),
+            inner: Artists { artists },
+        })
+    }
+
+    pub fn update(&mut self, fresh: Self) {
+        let selected = self.selected().cloned();
+        self.inner.artists = fresh.inner.artists;
+        self.index.anchor(self.inner.artists.len(), self.inner.artists.iter().position(|artist| Some(artist) == selected.as_ref()));
+    }
+
+    pub fn selected(&self) -> Option<&String> {
+        self.inner.artists.get(self.index.inner)
+    }
+}
+
+#[async_trait]
+impl BlockTrait for IndexedBlock<Artists> {
+    async fn active_event(state: &mut State, cmd: Command) -> Result<()> {
+        let artist = match &state.chunks.centre.inner.main {
+            MainBlock::Artists(artists) => artists.selected().cloned(),
+            _ => None,
+        };
+
+        match (cmd, artist) {
+            (Command::Select, Some(artist)) => Chunks::load(state, Location::new(View::Albums(AlbumKind::Artist(artist))), Step::Visit),
+
+            _ => {}
         }
+
+        Ok(())
     }
+
+    async fn hovered_event(_state: &mut State, _cmd: Command) -> Result<()> { Ok(()) }
 }
 
-impl<B: Backend> Render<B> for Artists {
-    fn render(&self, f: &mut Frame<B>, state: &State<B>, layout_chunk: Rect) {
+impl<B: Backend + Send> Render<B> for IndexedBlock<Artists> {
+    fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
         let highlight_state = (
-            state.blocks.is_active(Blokka::Main),
-            state.blocks.is_hovered(Blokka::Main)
+            state.chunks.is_active(BlockKind::Centre),
+            state.chunks.is_hovered(BlockKind::Centre)
         );
 
-        // let items: Vec<ListItem> = Vec::new(); 
-
-        // selectable_list(
-        //     f,
-        //     state,
-        //     layout_chunk,
-        //     " Artists ",
-        //     items,
-        //     highlight_state,
-        //     Some(self.index.inner)
-        // );
+        selectable_list(
+            f,
+            state,
+            layout_chunk,
+            " Artists ",
+            &self.inner.artists,
+            |artist| ListItem::new(Span::from(artist.as_str())),
+            highlight_state,
+            &self.index
+        );
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,202 @@
+use super::State;
+use super::search::{ load_history, remember };
+use crate::Render;
+use crate::command::{ Command, NAMES };
+use crate::event::{ Key, key };
+use crate::query;
+use crate::client::StrofaClient;
+use mpd_client::{ Client, commands };
+use anyhow::Result;
+use tui::{
+    Frame,
+    backend::Backend,
+    layout::Rect,
+    style::Style,
+    text::{ Span, Spans },
+    widgets::{ Clear, Paragraph }
+};
+
+const HISTORY: &str = "command_history";
+const BLOCKS: [&str; 6] = ["top_left", "top_right", "left_top", "left_bottom", "bottom", "centre"];
+
+// a `:` prompt along the bottom taking commands as they are written in the
+// config, eg. `volume 40` or `add-to-playlist Road Trip`
+pub struct CommandLine {
+    pub input: String,
+    // why the last command could not be run, shown until it is edited
+    pub error: Option<String>,
+    // entered commands, oldest first
+    history: Vec<String>,
+    // the history entry being shown, and what was typed before going through them
+    recalled: Option<(usize, String)>,
+    // the line before the word completed, and the candidates tab goes through
+    completing: Option<(String, Vec<String>, usize)>,
+    // for completing arguments
+    playlists: Vec<String>,
+    outputs: Vec<String>,
+}
+
+impl CommandLine {
+    pub async fn new(client: &Client) -> Result<Self> {
+        let playlists = client.command(commands::GetPlaylists).await?.into_iter().map(|playlist| playlist.name).collect();
+        let outputs = client.outputs().await?.into_iter().map(|output| output.name).collect();
+
+        Ok(Self {
+            input: String::new(),
+            error: None,
+            history: load_history(HISTORY),
+            recalled: None,
+            completing: None,
+            playlists,
+            outputs,
+        })
+    }
+
+    // keys bound to nothing are typed
+    pub fn input(&mut self, key: Key) {
+        if let key!(Char(c)) = key {
+            self.input.push(c);
+            self.edited();
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+        self.edited();
+    }
+
+    // removes the last word along with the whitespace after it
+    pub fn delete_word(&mut self) {
+        let start = self.input.trim_end().rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        self.input.truncate(start);
+        self.edited();
+    }
+
+    pub fn clear(&mut self) {
+        self.input.clear();
+        self.edited();
+    }
+
+    fn edited(&mut self) {
+        self.error = None;
+        self.completing = None;
+    }
+
+    pub fn recall_previous(&mut self) {
+        let i = match &self.recalled {
+            Some((0, _)) => return,
+            Some((i, _)) => i - 1,
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+
+        let draft = match self.recalled.take() {
+            Some((_, draft)) => draft,
+            None => self.input.clone(),
+        };
+
+        self.input = self.history[i].clone();
+        self.recalled = Some((i, draft));
+        self.edited();
+    }
+
+    pub fn recall_next(&mut self) {
+        match self.recalled.take() {
+            Some((i, draft)) if i + 1 == self.history.len() => self.input = draft,
+            Some((i, draft)) => {
+                self.input = self.history[i + 1].clone();
+                self.recalled = Some((i + 1, draft));
+            },
+
+            None => return,
+        }
+
+        self.edited();
+    }
+
+    // the command typed, remembered for next time, or nothing with the
+    // reason it is not one shown instead
+    pub fn command(&mut self) -> Option<Command> {
+        match self.input.parse() {
+            Ok(cmd) => {
+                self.recalled = None;
+                remember(HISTORY, &mut self.history, self.input.trim());
+                Some(cmd)
+            },
+
+            Err(e) => {
+                self.error = Some(e);
+                None
+            },
+        }
+    }
+
+    // completes the word being typed with the first candidate, then with the
+    // next one on every further tab
+    pub fn complete(&mut self) {
+        if let Some((line, candidates, i)) = &mut self.completing {
+            *i = (*i + 1) % candidates.len();
+            self.input = format!("{}{}", line, candidates[*i]);
+            return;
+        }
+
+        let (start, candidates) = self.candidates();
+        let word = self.input[start..].replace('-', "_").to_lowercase();
+        let candidates: Vec<String> = candidates
+            .into_iter()
+            .filter(|candidate| candidate.replace('-', "_").to_lowercase().starts_with(&word))
+            .collect();
+
+        if let Some(first) = candidates.first() {
+            let line = self.input[..start].to_string();
+            self.input = format!("{}{}", line, first);
+            self.completing = Some((line, candidates, 0));
+        }
+    }
+
+    // where the word being completed starts, and what it could be: the name
+    // of a command, or what that command takes
+    fn candidates(&self) -> (usize, Vec<String>) {
+        let split = match self.input.find(char::is_whitespace) {
+            Some(split) => split,
+            None => return (0, NAMES.iter().map(|name| name.to_string()).collect()),
+        };
+
+        let name = self.input[..split].replace('-', "_");
+        let start = self.input[split..].find(|c: char| !c.is_whitespace()).map_or(self.input.len(), |i| split + i);
+
+        match name.as_str() {
+            // names can have spaces, so they take up the rest of the line
+            "add_to_playlist" | "save_queue" => (start, self.playlists.clone()),
+            "toggle_output" => (start, self.outputs.clone()),
+            "focus" => (start, BLOCKS.iter().map(|blk| blk.to_string()).collect()),
+
+            // only the last word of a query
+            "search" => {
+                let word = self.input.rfind(char::is_whitespace).map_or(start, |i| (i + 1).max(start));
+                (word, query::PREFIXES.iter().map(|tag| format!("{}:", tag)).collect())
+            },
+
+            _ => (start, Vec::new()),
+        }
+    }
+}
+
+impl<B: Backend + Send> Render<B> for CommandLine {
+    fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
+        let mut line = vec![
+            Span::styled(":", Style::default().fg(state.theme.selected)),
+            Span::styled(self.input.as_str(), Style::default().fg(state.theme.text)),
+        ];
+
+        if let Some(error) = &self.error {
+            line.push(Span::styled(format!("  {}", error), Style::default().fg(state.theme.error_text)));
+        }
+
+        f.render_widget(Clear, layout_chunk);
+        f.render_widget(Paragraph::new(Spans::from(line)), layout_chunk);
+
+        let cursor = layout_chunk.x + 1 + self.input.chars().count() as u16;
+        f.set_cursor(cursor.min(layout_chunk.right().saturating_sub(1)), layout_chunk.y);
+    }
+}
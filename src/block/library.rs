@@ -1,9 +1,9 @@
-use super::{ Index, State, Render, get_color, selectable_list };
-use mpd_client::{ Client, commands, commands::responses::Playlist };
+use super::{ Index, State, Render, TrackKind, AlbumKind, selectable_list, border_note, spinner, count };
 use tui::{ 
     Frame,
     backend::Backend, 
     layout::{ Rect }, 
+    style::Style,
     text::Span, 
     widgets::ListItem
 };
@@ -12,15 +12,17 @@ pub struct Library {
    pub entries: [&'static str; 5]
 }
 
-use crate::event::Key;
+use crate::command::Command;
 use crate::block::{ BlockTrait, IndexedBlock };
+use crate::chunk::{ Chunks, Step };
+use crate::history::{ View, Location };
 use crate::chunk::BlockKind;
 use async_trait::async_trait;
 use anyhow::Result;
 
 impl IndexedBlock<Library> {
-    pub async fn new() -> Result<Self> {
-        Ok(Self {
+    pub fn new() -> Self {
+        Self {
             index: Index::new(5),
             inner: Library { 
                 entries: [
@@ -31,34 +33,34 @@ impl IndexedBlock<Library> {
                     "Podcasts"
                 ],
             }  
-        })
+        }
     }
 }
 
 #[async_trait]
 impl BlockTrait for IndexedBlock<Library> {
-    async fn active_event(state: &mut State, key: Key) {
-        match key {
-            Key::Up => state.chunks.centre.inner.left_chunk.inner.top.index.dec(),
-            Key::Down => state.chunks.centre.inner.left_chunk.inner.top.index.inc(),
-            // Key::Enter => {
-            //     let index = state.chunks.library.index.inner;
-            //     let main_block = match state.chunks.library.entries[index] {
-            //         "Queue" => MainBlock::Queue(Queue::new(&state.client).await.unwrap()),
-            //         "Tracks" => MainBlock::Tracks(Tracks::new(TrackKind::All, &state.client).await.unwrap()),
-            //         "Albums" => MainBlock::Albums(Albums::new(AlbumKind::All).await),
-            //         "Artists" => MainBlock::Artists(Artists::new().await),
-            //         "Podcasts" => MainBlock::Podcasts(Podcasts::new().await),
-            //         _ => panic!("view not found"),
-            //     };
+    async fn active_event(state: &mut State, cmd: Command) -> Result<()> {
+        match cmd {
+            Command::Select => {
+                let library = &state.chunks.centre.inner.left_chunk.inner.top;
+                let view = match library.inner.entries.get(library.index.inner) {
+                    Some(&"Queue") => View::Queue,
+                    Some(&"Tracks") => View::Tracks(TrackKind::All),
+                    Some(&"Albums") => View::Albums(AlbumKind::All),
+                    Some(&"Artists") => View::Artists,
+                    // "Podcasts" => MainBlock::Podcasts(Podcasts::new().await),
+                    _ => return Ok(()),
+                };
 
-            //     state.chunks.set_main(main_block);
-            // }
+                Chunks::load(state, Location::new(view), Step::Visit);
+            }
             _ => {},
         }
+
+        Ok(())
     }
 
-    async fn hovered_event(state: &mut State, key: Key) {}
+    async fn hovered_event(_state: &mut State, _cmd: Command) -> Result<()> { Ok(()) }
 }
 
 impl<B: Backend + Send> Render<B> for IndexedBlock<Library> {
@@ -68,19 +70,27 @@ impl<B: Backend + Send> Render<B> for IndexedBlock<Library> {
             state.chunks.is_hovered(BlockKind::LeftTop)
         );
 
-        let items: Vec<ListItem> = self.inner.entries
-            .into_iter()
-            .map(|i| ListItem::new(Span::raw(i)))
-            .collect();
-
         selectable_list(
             f,
             state,
             layout_chunk,
             " Library ",
-            items,
+            &self.inner.entries,
+            |i| ListItem::new(Span::raw(*i)),
             highlight_state,
-            Some(self.index.inner)
+            &self.index
         );
+
+        // the library being indexed, or why it couldn't be, on the top border
+        if let Some(e) = &state.index_error {
+            border_note(f, layout_chunk, &format!("couldn't index: {}", e), Style::default().fg(state.theme.error_text));
+        } else if state.indexing.is_some() {
+            let text = match state.index_progress {
+                Some((done, total)) => format!("{} indexing {}/{}", spinner(), count(done), count(total)),
+                None => format!("{} indexing", spinner()),
+            };
+
+            border_note(f, layout_chunk, &text, Style::default().fg(state.theme.hint));
+        }
     }  
 }
\ No newline at end of file
@@ -1,9 +1,8 @@
-// mod search;
-// pub use search::Search;
-// pub use search::SearchResults;
+mod search;
+pub use search::{ Search, SearchResults, Found };
 
-// mod sort;
-// pub use sort::Sort;
+mod sort;
+pub use sort::{ Sort, Sorting };
 
 mod library;
 pub use library::Library;
@@ -11,312 +10,401 @@ pub use library::Library;
 mod playlists;
 pub use playlists::Playlists;
 
-// mod playbar;
-// pub use playbar::Playbar;
+mod popup;
+pub use popup::{ Popup, Confirm, PlaylistPicker, Help };
+
+mod command_line;
+pub use command_line::CommandLine;
+
+mod playbar;
+pub use playbar::Playbar;
 
 // mod podcasts;
 // pub use podcasts::Podcasts;
 
-// mod tracks;
-// pub use tracks::{ Tracks, TrackKind };
+mod tracks;
+pub use tracks::{ Tracks, TrackKind };
 
-// mod queue;
-// pub use queue::Queue;
+mod queue;
+pub use queue::Queue;
 
-// mod albums;
-// pub use albums::{ Albums, AlbumKind };
+mod albums;
+pub use albums::{ Albums, Album, AlbumKind };
 
-// mod artists;
-// pub use artists::Artists;
+mod artists;
+pub use artists::Artists;
 
 use crate::Render; 
 use crate::state::State;
 use crate::theme::get_color;
-use crate::event::Key;
-
-use std::collections::VecDeque;
+use crate::command::Command;
+use crate::key::Keymap;
+use crate::history::{ View, Location };
+use crate::index::LibraryIndex;
+use crate::client::{ Progress, album_artist };
+use std::sync::{ Arc, Mutex, PoisonError };
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+use std::collections::HashMap;
 use async_trait::async_trait;
-use std::marker::PhantomData;
-use mpd_client::commands::responses::{ Song, SongInQueue, Playlist, PlayState };
-use mpd_client::{ Client, commands };
+use mpd_client::Client;
 use anyhow::Result;
 
 use tui::{
     backend::Backend,
-    layout::{ Constraint, Direction, Layout, Rect },
+    layout::{ Alignment, Constraint, Rect },
     style::{ Modifier, Style },
-    text::{ Span, Text },
+    text::Span,
     widgets::{ Block, Borders, BorderType, List, ListItem, ListState, Paragraph, Row, Table },
     Frame,
 };
 
-// pub struct Blocks<B> {    
-    // pub search: StandardBlock<Search>,
-    // pub sort: IndexedBlock<Sort>,
-    // pub library: IndexedBlock<Library>,
-    // pub playlists: IndexedBlock<Playlists>,
-    // pub main_block: Box<dyn BlockTrait>,
-    // pub playbar: StandardBlock<Playbar>,
-    // pub popup_block: Option<BlokkaK<dyn Popup>>, 
-//     pub active: Option<BlockKind>,
-//     pub hovered: BlockKind,
-//     pub hover_history: VecDeque<BlockKind>,
-//     _backend: PhantomData<B>,
-// }
-
-// impl<B: Send + Backend> Blocks<B> {
-//     pub async fn new(client: &Client) -> Result<Self> {
-//         Ok(Self {
-//             // search: Search::default(),
-//             // sort: Sort::new().await,
-//             // library: IndexedBlock::<Library>::new().await?,
-//             // playlists: IndexedBlock::<Playlists>::new(client).await?,
-//             // playbar: Playbar::new(client).await,
-//             // main: MainBlock::Queue(Queue::new(client).await?),
-//             active: None,
-//             hovered: BlockKind::LeftTop,
-//             hover_history: VecDeque::new(),
-//             _backend: PhantomData,
-//         })
-//     }
-
-    // pub fn get_block_mut(&mut self, kind: BlockKind) -> &mut dyn BlockTrait<B> {
-    //     match kind {
-    //         BlockKind::LeftTop => &mut self.library as &mut dyn BlockTrait<B>,
-    //         BlockKind::Playlists => &mut self.playlists as &mut dyn BlockTrait<B>,
-    //         _ => todo!()
-    //     }
-    // }
-
-    // pub fn is_hovered(&self, blk: BlockKind) -> bool {
-    //     if self.hovered==blk { return true; } false
-    // }
-
-    // pub fn is_active(&self, blk: BlockKind) -> bool {
-    //     if self.active==Some(blk) { return true; } false
-    // } 
-
-    // pub fn active_block(&mut self) -> Option<&mut dyn BlockTrait<B>> {
-    //     Some(self.get_block_mut(self.active?))
-    // }
-
-    // pub fn hovered_block(&mut self) -> &mut dyn BlockTrait<B> {
-    //     self.get_block_mut(self.hovered)
-    // }
-
-    // pub pub fn set_main(&mut self, blk: MainBlock) {
-    //     self.main = blk;
-    //     self.set_active(Blokka::Main);
-    // }
-
-    // pub fn set_active(&mut self, blk: BlockKind) {
-    //     self.active = Some(blk);
-    //     self.hovered = blk;
-    // }
-
-    // pub fn set_hover(&mut self, blk: BlockKind) {
-    //     self.hover_history.truncate(5);
-    //     self.hover_history.push_front(self.hovered.clone());
-    //     self.hovered = blk.clone();
-    // } 
-
-    // pub async fn active_event(state: &mut State, key: Key) {
-    //     match state.blocks.active {
-    //         // Some(BlockKind::Search) => Search::active_key_event(self, key).await,
-    //         // Some(BlockKind::Sort) => Sort::active_key_event(self, key).await,
-    //         Some(BlockKind::LeftTop) => IndexedBlock::<Library>::active_event(state, key).await,
-    //         Some(BlockKind::Playlists) => IndexedBlock::<Playlists>::active_event(state, key).await,
-
-        //     Some(BlockKind::Main) => { 
-        //         match key {
-        //             Key::Up => self.blocks.main.index().dec(),
-        //             Key::Down => self.blocks.main.index().inc(),
-        //             _ => {}
-        //         }
-
-        //         match &self.blocks.main {
-        //             MainBlock::Tracks(x) => {
-        //                 match key {
-        //                     Key::Enter => x.play(&self.client, x.index.inner).await,
-        //                     // Key::Char('A') => self.client.add_song_to_playlist(x.songs.get(x.index.inner).unwrap()).await
-        //                     _ => {}
-        //                 }
-        //             },
-
-        //             MainBlock::Queue(x) => {
-        //                 // x.active_key_event(self, key);
-        //                 // match key {
-        //                 //     Key::Enter => x.play(self.client.clone(), x.index.inner).await,
-        //                 //     Key::Char('c') => self.client.clear_queue().await.unwrap(),
-        //                 //     // Key::Char('p') => self.client.proritise_song_in_queue(x.index.inner)
-        //                 //     // Key::Char('w') => self.client.move_song_up_in_queue(x.songs.get(x.index.inner).unwrap()).await
-        //                 //     // Key::Char('s') => self.client.move_song_down_in_queue(x.songs.get(x.index.inner).unwrap()).await
-        //                 //     // Key::Char('A') => self.client.add_song_to_playlist(x.songs.get(x.index.inner).unwrap()).await
-        //                 //     // Key::Char('o') => x.jump_to_current_song().await
-        //                 //     _ => {}
-        //                 // }
-        //             },
-
-        //             _ => {}
-        //         }
-        //     },
-
-    //         _ => {}
-    //     }
-    // }
-
-    // pub async fn hovered_event(state: &mut State, key: Key) {
-    //     match state.blocks.hovered {
-    //         // BlockKind::Search => Search::hovered_key_event(self, key).await,
-    //         // BlockKind::Sort => Sort::hovered_key_event(self, key).await,
-    //         BlockKind::LeftTop => IndexedBlock::<Library>::hovered_event(state, key).await,
-    //         BlockKind::Playlists => IndexedBlock::<Playlists>::hovered_event(state, key).await,
-
-            // BlockKind::Main => {
-            //     match key {
-            //         Key::Up => self.blocks.set_hover(&BlockKind::Search),
-            //         Key::Left => {
-            //             for previous in self.blocks.hover_history.clone().into_iter() {
-            //                 if previous==BlockKind::LeftTop || previous==BlockKind::Playlists {
-            //                     self.blocks.set_hover(&previous);
-            //                     return;
-            //                 }
-            //             }
-
-            //             self.blocks.set_hover(&BlockKind::LeftTop)
-            //         },
-
-            //         Key::Right => self.blocks.set_hover(&BlockKind::Sort),
-            //         Key::Down => {
-            //             self.blocks.set_active(BlockKind::Main);
-            //             self.blocks.main.index().inc();
-            //         },
-
-            //         _ => {},
-            //     }
-            // },
-
-    //         _ => {}   
-    //     }
-
-    //     // common behaviour
-    //     match key {
-    //         Key::Enter => state.blocks.set_active(state.blocks.hovered),
-    //         _ => {}
-    //     }
-    // }   
-// }
-
-// #[derive(Copy, Clone, PartialEq)]
-// pub enum BlockKind {
-//     Search,
-//     Sort,
-//     Library,
-//     Playlists,
-//     Playbar,
-//     Main
-// }
-
-// #[derive(Copy, Clone, PartialEq)]
-// pub enum BlockPositions {
-//     TopLeft,
-//     TopRight,
-//     LeftTop,
-//     LeftBottom,
-//     Bottom,
-//     Centre
-// }
-
-// impl BlockPositions {
-//     pub fn event(state: &mut State) {
-        
-//     }
-// }
-
 #[async_trait]
 pub trait BlockTrait{
-    async fn active_event(state: &mut State, key: Key) where Self: Sized;
-    async fn hovered_event(state: &mut State, key: Key) where Self: Sized;// allow to do stuff on the active event, eg. main go down in index if down pressed while hovered
+    async fn active_event(state: &mut State, cmd: Command) -> Result<()> where Self: Sized;
+    async fn hovered_event(state: &mut State, cmd: Command) -> Result<()> where Self: Sized;// allow to do stuff on the active event, eg. main go down in index if down pressed while hovered
 }
 
 pub struct StandardBlock<T> {
-    inner: T
+    pub inner: T
 }
 
 pub struct IndexedBlock<T> {
-    index: Index,
-    inner: T
+    pub index: Index,
+    pub inner: T
+}
+
+// only one is ever held, so the search results being the largest costs nothing
+#[allow(clippy::large_enum_variant)]
+pub enum MainBlock {
+    SearchResults(StandardBlock<SearchResults>),
+    Artists(IndexedBlock<Artists>),
+    Albums(IndexedBlock<Albums>),
+    Tracks(IndexedBlock<Tracks>),
+    // Podcasts(Podcasts),
+    Queue(IndexedBlock<Queue>)
+}
+
+impl MainBlock {
+    pub fn index(&mut self) -> &mut Index {
+        match self {
+            MainBlock::SearchResults(x) => x.inner.index(),
+            MainBlock::Artists(x) => &mut x.index,
+            MainBlock::Albums(x) => &mut x.index,
+            MainBlock::Tracks(x) => &mut x.index,
+            MainBlock::Queue(x) => &mut x.index,
+        }
+    }
+
+    pub fn keymap(&self) -> Keymap {
+        match self {
+            MainBlock::SearchResults(_) => Keymap::SearchResults,
+            MainBlock::Artists(_) => Keymap::Artists,
+            MainBlock::Albums(_) => Keymap::Albums,
+            MainBlock::Tracks(_) => Keymap::Tracks,
+            MainBlock::Queue(_) => Keymap::Queue,
+        }
+    }
+
+    pub fn view(&self) -> View {
+        match self {
+            MainBlock::SearchResults(x) => View::Search(x.inner.query.clone()),
+            MainBlock::Artists(_) => View::Artists,
+            MainBlock::Albums(x) => View::Albums(x.inner.kind.clone()),
+            MainBlock::Tracks(x) => View::Tracks(x.inner.kind.clone()),
+            MainBlock::Queue(_) => View::Queue,
+        }
+    }
+
+    pub fn location(&self) -> Location {
+        let index = match self {
+            MainBlock::SearchResults(x) => x.inner.focused_index(),
+            MainBlock::Artists(x) => &x.index,
+            MainBlock::Albums(x) => &x.index,
+            MainBlock::Tracks(x) => &x.index,
+            MainBlock::Queue(x) => &x.index,
+        };
+
+        Location { view: self.view(), index: index.inner, offset: index.offset() }
+    }
+
+    // views are fetched again rather than kept around, so going back shows
+    // the library as it is now
+    pub async fn restore(location: &Location, client: &Client, library: &Arc<LibraryIndex>, progress: &Progress<'_>) -> Result<Self> {
+        let mut blk = match &location.view {
+            View::Search(query) => MainBlock::SearchResults(StandardBlock::<SearchResults>::new(query.clone(), client, library).await?),
+            View::Artists => MainBlock::Artists(IndexedBlock::<Artists>::new(client, library).await?),
+            View::Albums(kind) => MainBlock::Albums(IndexedBlock::<Albums>::new(kind.clone(), client, library, progress).await?),
+            View::Tracks(kind) => MainBlock::Tracks(IndexedBlock::<Tracks>::new(kind.clone(), client, library, progress).await?),
+            View::Queue => MainBlock::Queue(IndexedBlock::<Queue>::new(client).await?),
+        };
+
+        blk.index().restore(location.index, location.offset);
+        Ok(blk)
+    }
+
+    // takes the contents of the same view fetched again, following the
+    // selection to where it is now; a different view replaces this one
+    pub fn update(&mut self, fresh: MainBlock) {
+        match (&mut *self, fresh) {
+            (MainBlock::SearchResults(x), MainBlock::SearchResults(fresh)) => x.update(fresh),
+            (MainBlock::Artists(x), MainBlock::Artists(fresh)) => x.update(fresh),
+            (MainBlock::Albums(x), MainBlock::Albums(fresh)) => x.update(fresh),
+            (MainBlock::Tracks(x), MainBlock::Tracks(fresh)) => x.update(fresh),
+            (MainBlock::Queue(x), MainBlock::Queue(fresh)) => x.update(fresh),
+            (_, fresh) => *self = fresh,
+        }
+    }
+
+    pub fn sort(&mut self, sorting: &Sorting) {
+        match self {
+            MainBlock::SearchResults(x) => {
+                sorting.sort_songs(&mut x.inner.tracks.inner);
+                sorting.sort_albums(&mut x.inner.albums.inner);
+                x.inner.track_cells.clear();
+                x.inner.album_cells.clear();
+            },
+
+            MainBlock::Albums(x) => {
+                sorting.sort_albums(&mut x.inner.albums);
+                x.inner.cells.clear();
+            },
+
+            MainBlock::Tracks(x) => {
+                sorting.sort_songs(&mut x.inner.tracks);
+                x.inner.cells.clear();
+            },
+            MainBlock::Artists(_) | MainBlock::Queue(_) => {},
+        }
+    }
+
+    // search results focus the pane under the mouse, the other views are a single list
+    pub fn focus_at(&mut self, area: Rect, column: u16, row: u16) {
+        if let MainBlock::SearchResults(x) = self {
+            if let Some((pane, _)) = SearchResults::panes(area).into_iter().find(|(_, pane)| contains(*pane, column, row)) {
+                x.inner.focus = pane;
+            }
+        }
+    }
+
+    // selects the row under the mouse, telling whether there was one
+    pub fn select_at(&mut self, area: Rect, column: u16, row: u16) -> bool {
+        self.focus_at(area, column, row);
+
+        let hit = match self {
+            MainBlock::SearchResults(x) => x.inner.row_at(area, row),
+            MainBlock::Artists(x) => list_row(area, &x.index, row),
+            MainBlock::Albums(x) => table_row(area, &x.index, row),
+            MainBlock::Tracks(x) => table_row(area, &x.index, row),
+            MainBlock::Queue(x) => table_row(area, &x.index, row),
+        };
+
+        if let Some(i) = hit {
+            self.index().select(i);
+        }
+
+        hit.is_some()
+    }
+
+    // the songs behind the selected row, used to queue or play whatever is selected
+    pub async fn selected_uris(&self, client: &Client) -> Result<Vec<String>> {
+        Ok(match self {
+            MainBlock::SearchResults(x) => x.inner.selected_uris(client).await?,
+            MainBlock::Artists(x) => match x.selected() {
+                Some(artist) => Artists::songs(client, artist).await?.into_iter().map(|song| song.url).collect(),
+                None => Vec::new(),
+            },
+
+            MainBlock::Albums(x) => x.selected().map(|album| album.uris.clone()).unwrap_or_default(),
+            MainBlock::Tracks(x) => x.selected().map(|song| vec![song.url.clone()]).unwrap_or_default(),
+            // already queued
+            MainBlock::Queue(_) => Vec::new(),
+        })
+    }
+
+    // the album artist and album of the selected song or album, for going to either
+    pub fn selected_album(&self) -> Option<(String, String)> {
+        let song = match self {
+            MainBlock::SearchResults(x) => return x.inner.selected_album(),
+            MainBlock::Albums(x) => return x.selected().map(|album| (album.artist.clone(), album.name.clone())),
+            MainBlock::Tracks(x) => x.selected(),
+            MainBlock::Queue(x) => x.inner.songs.get(x.index.inner).map(|song| &song.song),
+            MainBlock::Artists(_) => None,
+        };
+
+        song.map(|song| (album_artist(song).to_string(), song.album().unwrap_or_default().to_string()))
+    }
+
 }
 
-// pub struct PopupBlock<T> {
-//     inner: T
-// }
-
-// pub enum MainBlock {
-//     SearchResults(SearchResults),
-//     Artists(Artists),
-//     Albums(Albums),
-//     Tracks(Tracks),
-//     Podcasts(Podcasts),
-//     Queue(Queue)
-// }
-
-// impl<B: Backend> Render<B> for MainBlock {
-//     fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
-//         match self {
-//             MainBlock::SearchResults(x) => x.render(f, state, layout_chunk),
-//             MainBlock::Artists(x) => x.render(f, state, layout_chunk),
-//             MainBlock::Albums(x) => x.render(f, state, layout_chunk),
-//             MainBlock::Tracks(x) => x.render(f, state, layout_chunk),
-//             MainBlock::Podcasts(x) => x.render(f, state, layout_chunk),
-//             MainBlock::Queue(x) => x.render(f, state, layout_chunk),
-
-//         }
-//     }
-// }
-
-// impl SelectableList for MainBlock {
-//     fn index(&mut self) -> &mut Index {
-//         match self {
-//             MainBlock::SearchResults(x) => x.index(), 
-//             MainBlock::Artists(x) => x.index(), 
-//             MainBlock::Albums(x) => x.index(), 
-//             MainBlock::Tracks(x) => x.index(), 
-//             MainBlock::Podcasts(x) => x.index(), 
-//             MainBlock::Queue(x) => x.index(),
-//         }
-//     }
-// }
+#[async_trait]
+impl BlockTrait for MainBlock {
+    async fn active_event(state: &mut State, cmd: Command) -> Result<()> {
+        match state.chunks.centre.inner.main {
+            MainBlock::SearchResults(_) => StandardBlock::<SearchResults>::active_event(state, cmd).await,
+            MainBlock::Artists(_) => IndexedBlock::<Artists>::active_event(state, cmd).await,
+            MainBlock::Albums(_) => IndexedBlock::<Albums>::active_event(state, cmd).await,
+            MainBlock::Tracks(_) => IndexedBlock::<Tracks>::active_event(state, cmd).await,
+            MainBlock::Queue(_) => IndexedBlock::<Queue>::active_event(state, cmd).await,
+        }
+    }
+
+    async fn hovered_event(_state: &mut State, _cmd: Command) -> Result<()> { Ok(()) }
+}
 
+impl<B: Backend + Send> Render<B> for MainBlock {
+    fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
+        match self {
+            MainBlock::SearchResults(x) => x.render(f, state, layout_chunk),
+            MainBlock::Artists(x) => x.render(f, state, layout_chunk),
+            MainBlock::Albums(x) => x.render(f, state, layout_chunk),
+            MainBlock::Tracks(x) => x.render(f, state, layout_chunk),
+            MainBlock::Queue(x) => x.render(f, state, layout_chunk),
+        }
+    }
+}
+
+pub fn contains(area: Rect, column: u16, row: u16) -> bool {
+    column >= area.x && column < area.right() && row >= area.y && row < area.bottom()
+}
+
+// the selected row of a list of `len` rows, and how far the list is scrolled
 pub struct Index {
     pub inner: usize,
-    max: usize,
+    len: usize,
+    // the first row shown and how many fit as of the last render, which only
+    // gets to borrow the block; atomic so blocks can still be shared across awaits
+    offset: AtomicUsize,
+    height: AtomicUsize,
 }
 
 impl Index {
-    pub fn new(max: usize) -> Self {
+    pub fn new(len: usize) -> Self {
         Index {
             inner: 0,
-            max: max,
+            len,
+            offset: AtomicUsize::new(0),
+            height: AtomicUsize::new(0),
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset.load(Ordering::Relaxed)
+    }
+
     pub fn dec(&mut self) {
-        if self.inner > 0 {
-            self.inner-=1;
-        }
+        self.up(1, false);
     }
 
     pub fn inc(&mut self) {
-        if self.inner < self.max {
-            self.inner+=1;
+        self.down(1, false);
+    }
+
+    // going past either end carries on from the other one when `wrap` is set
+    pub fn up(&mut self, n: usize, wrap: bool) {
+        match wrap && self.len > 0 {
+            true => self.inner = (self.inner + self.len - n % self.len) % self.len,
+            false => self.inner = self.inner.saturating_sub(n),
+        }
+    }
+
+    pub fn down(&mut self, n: usize, wrap: bool) {
+        match wrap && self.len > 0 {
+            true => self.inner = (self.inner + n % self.len) % self.len,
+            false => self.select(self.inner.saturating_add(n)),
         }
     }
+
+    // moves by as many rows as were shown last, scrolling along so the
+    // selection stays on the same line of the screen
+    pub fn page_up(&mut self, n: usize) {
+        let rows = (*self.height.get_mut()).max(1).saturating_mul(n);
+        *self.offset.get_mut() = self.offset.get_mut().saturating_sub(rows);
+        self.up(rows, false);
+    }
+
+    pub fn page_down(&mut self, n: usize) {
+        let rows = (*self.height.get_mut()).max(1).saturating_mul(n);
+        *self.offset.get_mut() = self.offset.get_mut().saturating_add(rows);
+        self.down(rows, false);
+    }
+
+    pub fn select(&mut self, inner: usize) {
+        self.inner = inner.min(self.len.saturating_sub(1));
+    }
+
+    // the selection and scrolling a list was left with, kept within it by
+    // the next render should it have changed since
+    pub fn restore(&mut self, inner: usize, offset: usize) {
+        self.select(inner);
+        *self.offset.get_mut() = offset;
+    }
+
+    // keeps the cursor where it was unless the list shrank underneath it
+    pub fn set_len(&mut self, len: usize) {
+        self.len = len;
+        self.select(self.inner);
+    }
+
+    // for a list that was reloaded or reordered: follows the row selected
+    // before to `row`, where it is now, keeping it on the same line of the
+    // screen; when it is gone the cursor stays put
+    pub fn anchor(&mut self, len: usize, row: Option<usize>) {
+        self.set_len(len);
+        if let Some(row) = row {
+            let line = self.inner.saturating_sub(*self.offset.get_mut());
+            self.select(row);
+            *self.offset.get_mut() = self.inner.saturating_sub(line);
+        }
+    }
+
+    // handles the commands moving through a list, telling whether `cmd` was one;
+    // `count` repeats them, or picks the row to jump to counting from 1
+    pub fn navigate(&mut self, cmd: &Command, count: Option<usize>, wrap: bool) -> bool {
+        let times = count.unwrap_or(1);
+        match (cmd, count) {
+            (Command::MoveUp, _) => self.up(times, wrap),
+            (Command::MoveDown, _) => self.down(times, wrap),
+            (Command::PageUp, _) => self.page_up(times),
+            (Command::PageDown, _) => self.page_down(times),
+            (Command::JumpToStart | Command::JumpToEnd, Some(row)) => self.select(row.saturating_sub(1)),
+            (Command::JumpToStart, None) => self.select(0),
+            (Command::JumpToEnd, None) => self.select(usize::MAX),
+            _ => return false,
+        }
+
+        true
+    }
+
+    // the first row to draw when `height` rows fit, scrolling no further than
+    // it takes to keep `scroll_off` rows either side of the selection in view
+    pub fn scroll(&self, height: usize, scroll_off: usize) -> usize {
+        let scroll_off = scroll_off.min(height.saturating_sub(1) / 2);
+        let offset = self.offset.load(Ordering::Relaxed)
+            .min(self.inner.saturating_sub(scroll_off))
+            .max((self.inner + scroll_off + 1).saturating_sub(height))
+            .min(self.len.saturating_sub(height));
+
+        self.offset.store(offset, Ordering::Relaxed);
+        self.height.store(height, Ordering::Relaxed);
+        offset
+    }
 }
 
-fn selectable_list<B>(f: &mut Frame<B>, state: &State, layout_chunk: Rect, title: &str, items: Vec<ListItem>, highlight_state: (bool, bool), selected_index: Option<usize>) where B: Backend {
+// only the items in view are made, so drawing costs the same however long the list is
+#[allow(clippy::too_many_arguments)]
+fn selectable_list<B, T>(f: &mut Frame<B>, state: &State, layout_chunk: Rect, title: &str, items: &[T], item: impl Fn(&T) -> ListItem, highlight_state: (bool, bool), index: &Index) where B: Backend {
+    // scrolled here rather than by tui, which only scrolls as little as it can
+    let height = layout_chunk.height.saturating_sub(2) as usize;
+    let offset = index.scroll(height, state.layout.scroll_off);
+    let items: Vec<ListItem> = items.iter().skip(offset).take(height).map(item).collect();
+
     let mut list_state = ListState::default();
-    list_state.select(selected_index);
+    list_state.select(index.inner.checked_sub(offset));
 
     let colour = get_color(highlight_state, state.theme);
     let list = List::new(items).block(Block::default()
@@ -331,30 +419,57 @@ pub struct TableHeaderItem<'a> {
     width: u16
 }
 
-fn selectable_table<B>(f: &mut Frame<B>, state: &State, layout_chunk: Rect, title: &str, header: &[TableHeaderItem], items: Vec<Vec<String>>, selected_index: usize, highlight_state: (bool, bool)) 
+// the cells of a table's rows, formatted the first time each row is drawn and
+// kept until the rows change, so scrolling back and forth formats nothing twice
+#[derive(Default)]
+pub struct Cells {
+    // drawing only borrows the block, and blocks are shared across awaits
+    rows: Mutex<HashMap<usize, Vec<String>>>,
+}
+
+impl Cells {
+    // for whenever the rows are replaced or reordered
+    pub fn clear(&mut self) {
+        self.rows.get_mut().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+
+    fn get<T>(&self, i: usize, item: &T, format: impl Fn(&T) -> Vec<String>) -> Vec<String> {
+        let mut rows = self.rows.lock().unwrap_or_else(PoisonError::into_inner);
+        rows.entry(i).or_insert_with(|| format(item)).clone()
+    }
+}
+
+// like `selectable_list`, only making the rows in view, out of the cells
+// cached for them; the playing row is marked in its first column
+#[allow(clippy::too_many_arguments)]
+fn selectable_table<B, T>(f: &mut Frame<B>, state: &State, layout_chunk: Rect, title: &str, header: &[TableHeaderItem], items: &[T], cells: &Cells, format: impl Fn(&T) -> Vec<String>, index: &Index, playing_index: Option<usize>, highlight_state: (bool, bool)) 
 where B: Backend {
     let widths = header
         .iter()
         .map(|h| Constraint::Length(h.width))
         .collect::<Vec<tui::layout::Constraint>>();
 
-    let padding = 5;
-    let offset = layout_chunk
-        .height
-        .checked_sub(padding)
-        .and_then(|height| selected_index.checked_sub(height as usize))
-        .unwrap_or(0);
+    // below the borders and the header
+    let height = layout_chunk.height.saturating_sub(3) as usize;
+    let offset = index.scroll(height, state.layout.scroll_off);
 
     let colour = get_color(highlight_state, state.theme);
-    let rows = items.iter().skip(offset).enumerate().map(|(i, item)| {
-        let formatted_row = item.clone();
+    let rows = items.iter().enumerate().skip(offset).take(height).map(|(i, item)| {
+        let mut row = cells.get(i, item, &format);
         let mut style = Style::default().fg(state.theme.text);
 
-        if Some(i) == selected_index.checked_sub(offset) {
+        if Some(i) == playing_index {
+            style = Style::default().fg(state.theme.active);
+            if let Some(first) = row.first_mut() {
+                *first = String::from("▶");
+            }
+        }
+
+        if i == index.inner {
             style = colour.add_modifier(Modifier::BOLD);
         }
 
-        Row::new(formatted_row).style(style)
+        Row::new(row).style(style)
     });
 
     let table = Table::new(rows)
@@ -372,8 +487,138 @@ where B: Backend {
     f.render_widget(table, layout_chunk);
 }
 
+// the item of a list drawn by `selectable_list` on terminal row `row`
+pub fn list_row(layout_chunk: Rect, index: &Index, row: u16) -> Option<usize> {
+    let height = layout_chunk.height.checked_sub(2)? as usize;
+    let line = row.checked_sub(layout_chunk.y + 1)? as usize;
+
+    (line < height).then(|| index.offset() + line).filter(|&i| i < index.len())
+}
+
+// the row of a table drawn by `selectable_table` on terminal row `row`, below its header
+pub fn table_row(layout_chunk: Rect, index: &Index, row: u16) -> Option<usize> {
+    let line = row.checked_sub(layout_chunk.y + 2)? as usize;
+    let height = layout_chunk.height.checked_sub(3)? as usize;
+
+    (line < height).then(|| index.offset() + line).filter(|&i| i < index.len())
+}
+
+// text drawn right-aligned over the top border of a block, as long as it fits
+pub fn border_note<B: Backend>(f: &mut Frame<B>, layout_chunk: Rect, text: &str, style: Style) {
+    let width = (text.chars().count() as u16 + 2).min(layout_chunk.width.saturating_sub(2));
+    let area = Rect::new(layout_chunk.right().saturating_sub(width + 1), layout_chunk.y, width, 1);
+    f.render_widget(Paragraph::new(Span::styled(format!(" {} ", text), style)).alignment(Alignment::Right), area);
+}
+
+// turns with the time, so that redrawing on every tick animates it
+pub fn spinner() -> char {
+    const FRAMES: [char; 8] = ['⣾', '⣽', '⣻', '⢿', '⡿', '⣟', '⣯', '⣷'];
+    let elapsed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    FRAMES[(elapsed.as_millis() / crate::TICK_RATE.as_millis()) as usize % FRAMES.len()]
+}
+
+// eg. `950` or `12k`, short enough for a border
+pub fn count(n: usize) -> String {
+    match n {
+        0..=999 => n.to_string(),
+        _ => format!("{}k", n / 1000),
+    }
+}
+
 fn get_percentage_width(width: u16, percentage: f32) -> u16 {
      let padding = 3;
-     let width = width - padding;
+     let width = width.saturating_sub(padding);
      (f32::from(width) * percentage) as u16
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // as if drawn `height` rows tall
+    fn shown(len: usize, height: usize) -> Index {
+        let index = Index::new(len);
+        index.scroll(height, 0);
+        index
+    }
+
+    #[test]
+    fn wrapping() {
+        let mut index = Index::new(3);
+        index.up(1, true);
+        assert_eq!(index.inner, 2);
+        index.down(1, true);
+        assert_eq!(index.inner, 0);
+        index.down(4, true);
+        assert_eq!(index.inner, 1);
+
+        index.up(5, false);
+        assert_eq!(index.inner, 0);
+        index.down(5, false);
+        assert_eq!(index.inner, 2);
+    }
+
+    #[test]
+    fn paging() {
+        let mut index = shown(10, 4);
+        index.page_down(1);
+        assert_eq!((index.inner, index.offset()), (4, 4));
+        index.page_down(2);
+        assert_eq!(index.inner, 9);
+        assert_eq!(index.scroll(4, 0), 6);
+
+        index.page_up(1);
+        assert_eq!((index.inner, index.offset()), (5, 2));
+        index.page_up(5);
+        assert_eq!((index.inner, index.offset()), (0, 0));
+    }
+
+    #[test]
+    fn jumping() {
+        let mut index = Index::new(10);
+        assert!(index.navigate(&Command::JumpToEnd, None, false));
+        assert_eq!(index.inner, 9);
+        assert!(index.navigate(&Command::JumpToStart, None, false));
+        assert_eq!(index.inner, 0);
+
+        // counting from 1, and no further than the end
+        index.navigate(&Command::JumpToStart, Some(4), false);
+        assert_eq!(index.inner, 3);
+        index.navigate(&Command::JumpToEnd, Some(40), false);
+        assert_eq!(index.inner, 9);
+
+        assert!(!index.navigate(&Command::Select, None, false));
+    }
+
+    #[test]
+    fn empty() {
+        let mut index = shown(0, 4);
+        for cmd in [Command::MoveUp, Command::MoveDown, Command::PageUp, Command::PageDown, Command::JumpToStart, Command::JumpToEnd] {
+            index.navigate(&cmd, None, true);
+            index.navigate(&cmd, Some(3), false);
+            assert_eq!(index.inner, 0);
+        }
+
+        assert_eq!(index.scroll(4, 2), 0);
+    }
+
+    #[test]
+    fn anchoring() {
+        let mut index = shown(20, 5);
+        index.select(12);
+        index.scroll(5, 0);
+        assert_eq!(index.offset(), 8);
+
+        // the selected row moved down by three, and stays on the same line
+        index.anchor(20, Some(15));
+        assert_eq!((index.inner, index.offset()), (15, 11));
+
+        // gone, the cursor stays put
+        index.anchor(20, None);
+        assert_eq!(index.inner, 15);
+
+        // gone along with the rows after it
+        index.anchor(10, None);
+        assert_eq!(index.inner, 9);
+    }
+}
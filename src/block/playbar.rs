@@ -1,53 +1,219 @@
-use super::{ State, Render };
-use std::time::Duration;
-use mpd_client::{ Client, commands, commands::responses::{ SongInQueue, PlayState }};
-use tui::{ 
+use super::{ StandardBlock, State, Render, contains };
+use crate::client::StrofaClient;
+use mpd_client::{ Client, commands, commands::{ SingleMode, responses::{ SongInQueue, PlayState } } };
+use anyhow::Result;
+use std::time::{ Duration, Instant };
+use tui::{
     Frame,
-    backend::Backend, 
-    layout::{ Rect }, 
-    text::Span, 
-    style::Style,
-    widgets::{ Block, Borders }
+    backend::Backend,
+    layout::{ Alignment, Constraint, Direction, Layout, Rect },
+    text::{ Span, Spans },
+    style::{ Modifier, Style },
+    widgets::{ Block, Borders, BorderType, Gauge, Paragraph }
 };
 
 pub struct Playbar {
     pub song: Option<SongInQueue>,
+    pub play_state: PlayState,
     pub volume: u8,
     pub shuffle: bool,
     pub repeat: bool,
+    pub single: SingleMode,
+    pub consume: bool,
+    pub elapsed: Option<Duration>,
+    pub duration: Option<Duration>,
+    // when `elapsed` was reported, mpd only tells again once something changes
+    pub fetched: Instant,
     // pub history: Vec<SingInQueue>, //depends: do i like mpds current "previous" function ?
 }
 
+impl Default for Playbar {
+    fn default() -> Self {
+        Self {
+            song: None,
+            play_state: PlayState::Stopped,
+            volume: 0,
+            shuffle: false,
+            repeat: false,
+            single: SingleMode::Disabled,
+            consume: false,
+            elapsed: None,
+            duration: None,
+            fetched: Instant::now(),
+        }
+    }
+}
+
 impl Playbar {
-    pub async fn new(client: &Client) -> Self {
-        let status = client.command(commands::Status).await.unwrap();
+    pub async fn fetch(client: &Client) -> Result<Self> {
+        let status = client.command(commands::Status).await?;
+        let song = client.command(commands::CurrentSong).await?;
 
-        Self { 
-            song: client.command(commands::CurrentSong).await.unwrap(),
+        Ok(Self {
+            duration: status.duration.or_else(|| song.as_ref()?.song.duration),
+            song,
+            play_state: status.state,
             volume: status.volume,
             shuffle: status.random,
-            repeat: status.repeat
+            repeat: status.repeat,
+            single: status.single,
+            consume: status.consume,
+            elapsed: status.elapsed,
+            fetched: Instant::now(),
+        })
+    }
+
+    // moved on by the time passed since the last status while playing, so the
+    // progress keeps up without asking mpd every tick
+    pub fn elapsed(&self) -> Option<Duration> {
+        let elapsed = match self.play_state {
+            PlayState::Playing => self.elapsed? + self.fetched.elapsed(),
+            _ => self.elapsed?,
+        };
+
+        Some(match self.duration {
+            Some(duration) => elapsed.min(duration),
+            None => elapsed,
+        })
+    }
+
+    // the title, the artist and album, and at the bottom the progress
+    fn rows(area: Rect) -> Vec<Rect> {
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)].as_ref())
+            .split(Block::default().borders(Borders::ALL).inner(area))
+    }
+}
+
+impl StandardBlock<Playbar> {
+    // empty until fetched once strofa has started
+    pub fn new() -> Self {
+        Self { inner: Playbar::default() }
+    }
+
+    // seeks to wherever the progress gauge was clicked, the playbar itself is
+    // refreshed once mpd reports the player changing
+    pub async fn seek_at(&self, client: &Client, area: Rect, column: u16, row: u16) -> Result<()> {
+        let progress = Playbar::rows(area)[3];
+        let duration = match self.inner.duration {
+            Some(duration) if !duration.is_zero() && self.inner.song.is_some() => duration,
+            _ => return Ok(()),
+        };
+
+        if contains(progress, column, row) {
+            let ratio = f64::from(column - progress.x) / f64::from(progress.width);
+            client.seek_to(duration.mul_f64(ratio)).await?;
         }
+
+        Ok(())
     }
 }
 
-impl<B: Backend> Render<B> for Playbar {
-    fn render(&self, f: &mut Frame<B>, state: &State<B>, layout_chunk: Rect) {
-        if let Some(song) = &self.song {
-            if let Some(title) = song.song.title() {
-                let playbar = Block::default()
-                    .title(Span::styled(title, Style::default().fg(state.theme.text)))
-                    .borders(Borders::NONE);
+// mm:ss, or h:mm:ss for anything an hour or longer
+pub(super) fn timestamp(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
 
-                f.render_widget(playbar, layout_chunk);  
-                return;
-            }        
-        } 
+impl<B: Backend + Send> Render<B> for StandardBlock<Playbar> {
+    fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
+        let playbar = &self.inner;
+        let text = Style::default().fg(state.theme.playbar_text);
+        let dimmed = Style::default().fg(state.theme.inactive);
 
-        let playbar = Block::default()
-            .title(Span::styled("poop", Style::default().fg(state.theme.text)))
-            .borders(Borders::NONE);
+        let mut block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(dimmed)
+            .style(Style::default().bg(state.theme.playbar_background));
 
-        f.render_widget(playbar, layout_chunk);
+        // why the last command failed, until the next key
+        if let Some(error) = &state.error {
+            block = block.title(Span::styled(format!(" {} ", error), Style::default().fg(state.theme.error_text)));
+        }
+
+        f.render_widget(block, layout_chunk);
+        let rows = Playbar::rows(layout_chunk);
+
+        let top = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(30)].as_ref())
+            .split(rows[0]);
+
+        let bottom = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(30)].as_ref())
+            .split(rows[1]);
+
+        let symbol = match playbar.play_state {
+            PlayState::Playing => "▶",
+            PlayState::Paused => "⏸",
+            PlayState::Stopped => "■",
+        };
+
+        let (title, details) = match &playbar.song {
+            Some(song) => {
+                let title = song.song.title().map(str::to_string)
+                    .unwrap_or_else(|| song.song.url.rsplit('/').next().unwrap_or_default().to_string());
+
+                let details = [song.song.artists().join(", "), song.song.album().unwrap_or_default().to_string()]
+                    .into_iter()
+                    .filter(|detail| !detail.is_empty())
+                    .collect::<Vec<String>>()
+                    .join(" — ");
+
+                (title, details)
+            },
+
+            None => (String::from("Nothing playing"), String::new()),
+        };
+
+        f.render_widget(Paragraph::new(Spans::from(vec![
+            Span::styled(format!("{} ", symbol), text),
+            Span::styled(title, text.add_modifier(Modifier::BOLD)),
+        ])), top[0]);
+
+        f.render_widget(Paragraph::new(Span::styled(details, dimmed)), bottom[0]);
+
+        // modes that are off are still listed, dimmed, so they keep their place
+        let mode = |label: &'static str, on: bool| Span::styled(label, if on { text } else { dimmed });
+        let single = match playbar.single {
+            SingleMode::Enabled => mode("single", true),
+            SingleMode::Oneshot => mode("oneshot", true),
+            SingleMode::Disabled => mode("single", false),
+        };
+
+        let modes = Paragraph::new(Spans::from(vec![
+            mode("shuffle", playbar.shuffle), Span::raw(" "),
+            mode("repeat", playbar.repeat), Span::raw(" "),
+            single, Span::raw(" "),
+            mode("consume", playbar.consume),
+        ])).alignment(Alignment::Right);
+
+        f.render_widget(modes, top[1]);
+        f.render_widget(Paragraph::new(Span::styled(format!("vol {}%", playbar.volume), text)).alignment(Alignment::Right), bottom[1]);
+
+        let elapsed = playbar.elapsed().unwrap_or_default();
+        let (ratio, label) = match playbar.duration {
+            Some(duration) if !duration.is_zero() => (
+                (elapsed.as_secs_f64() / duration.as_secs_f64()).clamp(0.0, 1.0),
+                format!("{} / {}", timestamp(elapsed), timestamp(duration)),
+            ),
+
+            // streams have no end to measure against
+            _ => (0.0, timestamp(elapsed)),
+        };
+
+        let progress = Gauge::default()
+            .gauge_style(Style::default().fg(state.theme.playbar_progress).bg(state.theme.playbar_background))
+            .ratio(ratio)
+            .label(Span::styled(label, Style::default().fg(state.theme.playbar_progress_text).add_modifier(Modifier::BOLD)))
+            .use_unicode(true);
+
+        f.render_widget(progress, rows[3]);
     }
-}
\ No newline at end of file
+}
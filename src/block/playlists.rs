@@ -1,9 +1,10 @@
-use super::{ IndexedBlock, BlockTrait, State, Render, Index, get_color, selectable_list };
-use mpd_client::{ Client, commands, commands::responses::Playlist };
-use crate::event::Key;
+use super::{ IndexedBlock, BlockTrait, TrackKind, State, Render, Index, selectable_list };
+use mpd_client::commands::responses::Playlist;
+use crate::command::Command;
 use async_trait::async_trait;
 use anyhow::Result;
-use crate::chunk::BlockKind;
+use crate::chunk::{ BlockKind, Chunks, Step };
+use crate::history::{ View, Location };
 use tui::{ 
     Frame,
     backend::Backend, 
@@ -17,31 +18,45 @@ pub struct Playlists {
 }
 
 impl IndexedBlock<Playlists> {
-    pub async fn new(client: &Client) -> Result<Self> {
-        Ok(Self {
-            index: Index::new(50),
-            inner: Playlists { entries: client.command(commands::GetPlaylists).await? }  
-        })
+    // empty until fetched once strofa has started
+    pub fn new() -> Self {
+        Self {
+            index: Index::new(0),
+            inner: Playlists { entries: Vec::new() }
+        }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.inner.entries.iter().map(|playlist| playlist.name.clone()).collect()
+    }
+
+    // the playlists fetched again, keeping the selected one selected
+    pub fn update(&mut self, entries: Vec<Playlist>) {
+        let selected = self.inner.entries.get(self.index.inner).map(|playlist| playlist.name.clone());
+        self.inner.entries = entries;
+        self.index.anchor(self.inner.entries.len(), self.inner.entries.iter().position(|playlist| Some(&playlist.name) == selected.as_ref()));
     }
 }
 
 #[async_trait]
 impl BlockTrait for IndexedBlock<Playlists> {
-    async fn active_event(state: &mut State, key: Key) {
-        match key {
-            // Key::Up => state.chunks.playlists.index.dec(),
-            // Key::Down => state.chunks.playlists.index.inc(),   
-            // Key::Enter => {
-            //     let index = state.chunks.playlists.index.inner;  
-            //     let name = state.chunks.playlists.entries.get(0).unwrap().name.to_string();
-            //     state.chunks.set_main(MainBlock::Tracks(Tracks::new(TrackKind::Playlist(name), &state.client).await.unwrap()));
-            // },
+    async fn active_event(state: &mut State, cmd: Command) -> Result<()> {
+        match cmd {
+            Command::Select => {
+                let playlists = &state.chunks.centre.inner.left_chunk.inner.bottom;
+                if let Some(playlist) = playlists.inner.entries.get(playlists.index.inner) {
+                    let view = View::Tracks(TrackKind::Playlist(playlist.name.to_string()));
+                    Chunks::load(state, Location::new(view), Step::Visit);
+                }
+            },
 
             _ => {}
         }  
+
+        Ok(())
     }
 
-    async fn hovered_event(state: &mut State, key: Key) {}
+    async fn hovered_event(_state: &mut State, _cmd: Command) -> Result<()> { Ok(()) }
 }
 
 impl<B: Backend + Send> Render<B> for IndexedBlock<Playlists> {
@@ -51,19 +66,15 @@ impl<B: Backend + Send> Render<B> for IndexedBlock<Playlists> {
             state.chunks.is_hovered(BlockKind::LeftBottom)
         );
 
-        let items: Vec<ListItem> = self.inner.entries
-            .iter()
-            .map(|i| ListItem::new(Span::from(i.name.as_str())))
-            .collect();
-
         selectable_list(
             f,
             state,
             layout_chunk,
             " Playlists ",
-            items,
+            &self.inner.entries,
+            |i| ListItem::new(Span::from(i.name.as_str())),
             highlight_state,
-            Some(self.index.inner)
+            &self.index
         );
     }    
 }
\ No newline at end of file
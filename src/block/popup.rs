@@ -0,0 +1,333 @@
+use super::{ IndexedBlock, State, Render, Index, CommandLine, selectable_list };
+use mpd_client::{ Client, commands };
+use crate::client::StrofaClient;
+use crate::command::Command;
+use crate::key::{ KeyBindings, KeySequence, Keymap };
+use crate::event::{ Key, key };
+use anyhow::Result;
+use tui::{
+    Frame,
+    backend::Backend,
+    layout::{ Constraint, Direction, Layout, Rect },
+    style::{ Modifier, Style },
+    text::{ Span, Spans },
+    widgets::{ Block, Borders, BorderType, Clear, List, ListItem, ListState, Paragraph }
+};
+
+// drawn over every other chunk, and takes no other bindings than its own while open
+pub enum Popup {
+    Confirm(Confirm),
+    PlaylistPicker(IndexedBlock<PlaylistPicker>),
+    Help(IndexedBlock<Help>),
+    CommandLine(CommandLine),
+}
+
+impl Popup {
+    pub fn keymap(&self) -> Keymap {
+        match self {
+            Popup::Confirm(_) => Keymap::Confirm,
+            Popup::PlaylistPicker(_) => Keymap::PlaylistPicker,
+            Popup::Help(_) => Keymap::Help,
+            Popup::CommandLine(_) => Keymap::CommandLine,
+        }
+    }
+
+    pub async fn event(state: &mut State, cmd: Command) -> Result<()> {
+        let popup = match state.chunks.popup.take() {
+            Some(popup) => popup,
+            None => return Ok(()),
+        };
+
+        // the command confirmed or entered runs once the popup is closed, so
+        // that it can open another one
+        let mut confirmed = None;
+        let mut entered = None;
+
+        // popups close themselves by not being put back
+        state.chunks.popup = match popup {
+            Popup::Confirm(confirm) => match cmd {
+                Command::Select => {
+                    confirmed = Some(confirm.command);
+                    None
+                },
+
+                Command::Cancel => None,
+                _ => Some(Popup::Confirm(confirm)),
+            },
+
+            Popup::PlaylistPicker(mut picker) => match cmd {
+                Command::Select => {
+                    if let Some(playlist) = picker.inner.playlists.get(picker.index.inner) {
+                        state.client.add_to_playlist(playlist, &picker.inner.uri).await?;
+                    }
+
+                    None
+                },
+
+                Command::Cancel => None,
+                _ => {
+                    picker.index.navigate(&cmd, None, state.layout.wrap);
+                    Some(Popup::PlaylistPicker(picker))
+                },
+            },
+
+            Popup::Help(mut help) => match cmd {
+                Command::DeleteBackward => { help.inner.filter.pop(); help.filtered(); Some(Popup::Help(help)) },
+                Command::ClearLine => { help.inner.filter.clear(); help.filtered(); Some(Popup::Help(help)) },
+                Command::Cancel | Command::Select => None,
+                _ => {
+                    help.index.navigate(&cmd, None, state.layout.wrap);
+                    Some(Popup::Help(help))
+                },
+            },
+
+            Popup::CommandLine(mut line) => match cmd {
+                // a line that does not parse stays open with the reason shown
+                Command::Select => match line.command() {
+                    Some(cmd) => {
+                        entered = Some((cmd, line));
+                        None
+                    },
+
+                    None => Some(Popup::CommandLine(line)),
+                },
+
+                Command::MoveUp => { line.recall_previous(); Some(Popup::CommandLine(line)) },
+                Command::MoveDown => { line.recall_next(); Some(Popup::CommandLine(line)) },
+                Command::Complete => { line.complete(); Some(Popup::CommandLine(line)) },
+                Command::DeleteBackward => { line.backspace(); Some(Popup::CommandLine(line)) },
+                Command::DeleteWord => { line.delete_word(); Some(Popup::CommandLine(line)) },
+                Command::ClearLine => { line.clear(); Some(Popup::CommandLine(line)) },
+                Command::Cancel => None,
+                _ => Some(Popup::CommandLine(line)),
+            },
+        };
+
+        // boxed, as the command could in turn lead back here
+        if let Some(cmd) = confirmed {
+            Box::pin(cmd.execute(state, None)).await?;
+        }
+
+        // and one that fails reopens the line to say why
+        if let Some((cmd, mut line)) = entered {
+            if let Err(e) = Box::pin(cmd.execute(state, None)).await {
+                line.error = Some(e.to_string());
+                state.chunks.popup = Some(Popup::CommandLine(line));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<B: Backend + Send> Render<B> for Popup {
+    fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
+        match self {
+            Popup::Confirm(x) => x.render(f, state, centered_rect(40, 20, layout_chunk)),
+            Popup::PlaylistPicker(x) => x.render(f, state, centered_rect(40, 50, layout_chunk)),
+            Popup::Help(x) => x.render(f, state, centered_rect(60, 70, layout_chunk)),
+            Popup::CommandLine(x) => x.render(f, state, Rect::new(layout_chunk.x, layout_chunk.bottom().saturating_sub(1), layout_chunk.width, 1)),
+        }
+    }
+}
+
+pub struct Confirm {
+    pub message: String,
+    pub command: Command,
+}
+
+impl Confirm {
+    pub fn new(message: &str, command: Command) -> Self {
+        Self { message: message.to_string(), command }
+    }
+}
+
+impl<B: Backend + Send> Render<B> for Confirm {
+    fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
+        let colour = Style::default().fg(state.theme.selected);
+        let text = vec![
+            Spans::from(Span::styled(self.message.as_str(), Style::default().fg(state.theme.text))),
+            Spans::from(""),
+            Spans::from(Span::styled("[y]es / [n]o", colour.add_modifier(Modifier::BOLD))),
+        ];
+
+        let confirm = Paragraph::new(text).block(Block::default()
+            .title(Span::styled(" Confirm ", colour))
+            .borders(Borders::ALL)
+            .border_style(colour)
+            .border_type(BorderType::Rounded));
+
+        f.render_widget(Clear, layout_chunk);
+        f.render_widget(confirm, layout_chunk);
+    }
+}
+
+pub struct PlaylistPicker {
+    pub uri: String,
+    pub playlists: Vec<String>,
+}
+
+impl IndexedBlock<PlaylistPicker> {
+    pub async fn new(client: &Client, uri: String) -> Result<Self> {
+        let playlists: Vec<String> = client.command(commands::GetPlaylists).await?
+            .into_iter()
+            .map(|playlist| playlist.name)
+            .collect();
+
+        Ok(Self {
+            index: Index::new(playlists.len()),
+            inner: PlaylistPicker { uri, playlists },
+        })
+    }
+}
+
+impl<B: Backend + Send> Render<B> for IndexedBlock<PlaylistPicker> {
+    fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
+        f.render_widget(Clear, layout_chunk);
+        selectable_list(
+            f,
+            state,
+            layout_chunk,
+            " Add to Playlist ",
+            &self.inner.playlists,
+            |name| ListItem::new(Span::from(name.as_str())),
+            (true, true),
+            &self.index
+        );
+    }
+}
+
+// the bindings in effect where it was opened, grouped by keymap
+pub struct Help {
+    pub bindings: Vec<(Keymap, KeySequence, Command)>,
+    pub filter: String,
+}
+
+enum HelpRow<'a> {
+    Keymap(Keymap),
+    Binding(&'a KeySequence, &'a Command),
+}
+
+impl Help {
+    // a binding is shown when the filter is part of its keys, its command or
+    // its description, ignoring case
+    fn rows(&self) -> Vec<HelpRow<'_>> {
+        let filter = self.filter.to_lowercase();
+        let mut rows = Vec::new();
+
+        for (keymap, keys, cmd) in &self.bindings {
+            let shown = [keys.to_string(), cmd.to_string(), cmd.describe()]
+                .iter()
+                .any(|text| text.to_lowercase().contains(&filter));
+
+            if !shown {
+                continue;
+            }
+
+            if !matches!(rows.iter().rev().find(|row| matches!(row, HelpRow::Keymap(_))), Some(HelpRow::Keymap(k)) if k == keymap) {
+                rows.push(HelpRow::Keymap(*keymap));
+            }
+
+            rows.push(HelpRow::Binding(keys, cmd));
+        }
+
+        rows
+    }
+}
+
+impl IndexedBlock<Help> {
+    pub fn new(keys: &KeyBindings, keymaps: &[Keymap]) -> Self {
+        let mut help = Self {
+            index: Index::new(0),
+            inner: Help { bindings: keys.bindings(keymaps), filter: String::new() },
+        };
+
+        help.filtered();
+        help
+    }
+
+    // keys bound to nothing are typed into the filter
+    pub fn input(&mut self, key: Key) {
+        if let key!(Char(c)) = key {
+            self.inner.filter.push(c);
+            self.filtered();
+        }
+    }
+
+    fn filtered(&mut self) {
+        self.index.set_len(self.inner.rows().len());
+        self.index.select(0);
+    }
+}
+
+impl<B: Backend + Send> Render<B> for IndexedBlock<Help> {
+    fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
+        let colour = Style::default().fg(state.theme.selected);
+        let text = Style::default().fg(state.theme.text);
+        let dimmed = Style::default().fg(state.theme.inactive);
+
+        let block = Block::default()
+            .title(Span::styled(" Help ", colour))
+            .borders(Borders::ALL)
+            .border_style(colour)
+            .border_type(BorderType::Rounded);
+
+        let inner = block.inner(layout_chunk);
+        f.render_widget(Clear, layout_chunk);
+        f.render_widget(block, layout_chunk);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(2), Constraint::Min(0)].as_ref())
+            .split(inner);
+
+        let filter = match self.inner.filter.is_empty() {
+            true => Span::styled("type to filter", dimmed),
+            false => Span::styled(self.inner.filter.as_str(), text),
+        };
+
+        f.render_widget(Paragraph::new(Spans::from(vec![Span::styled("/ ", colour), filter])), chunks[0]);
+
+        let width = self.inner.bindings.iter().map(|(_, keys, _)| keys.to_string().chars().count()).max().unwrap_or(0);
+        let items: Vec<ListItem> = self.inner.rows()
+            .into_iter()
+            .map(|row| match row {
+                HelpRow::Keymap(keymap) => ListItem::new(Span::styled(keymap.to_string(), colour.add_modifier(Modifier::BOLD))),
+                HelpRow::Binding(keys, cmd) => ListItem::new(Spans::from(vec![
+                    Span::styled(format!("  {:<width$}  ", keys.to_string(), width = width), colour),
+                    Span::styled(cmd.describe(), text),
+                    Span::styled(format!("  {}", cmd), dimmed),
+                ])),
+            })
+            .collect();
+
+        let offset = self.index.scroll(chunks[1].height as usize, state.layout.scroll_off);
+        let items: Vec<ListItem> = items.into_iter().skip(offset).collect();
+
+        let mut list_state = ListState::default();
+        list_state.select(self.index.inner.checked_sub(offset));
+
+        let list = List::new(items).highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, chunks[1], &mut list_state);
+    }
+}
+
+fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ].as_ref())
+        .split(r);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ].as_ref())
+        .split(vertical[1])[1]
+}
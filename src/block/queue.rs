@@ -1,60 +1,82 @@
-use super::{ Blokka, State, Render, TableHeaderItem, SelectableList, Index, selectable_table, get_percentage_width };
+use super::{ IndexedBlock, BlockTrait, MainBlock, Popup, PlaylistPicker, State, Render, TableHeaderItem, Index, Cells, selectable_table, get_percentage_width };
 use mpd_client::{ Client, commands, commands::responses::SongInQueue };
 use tui::{ backend::Backend, layout::Rect, Frame };
+use crate::chunk::BlockKind;
+use crate::client::StrofaClient;
+use crate::command::Command;
+use async_trait::async_trait;
 use anyhow::Result;
 
+#[derive(Default)]
 pub struct Queue {
-    pub index: Index,
     pub songs: Vec<SongInQueue>,
+    pub cells: Cells,
 }
 
-impl Queue {
+impl IndexedBlock<Queue> {
     pub async fn new(client: &Client) -> Result<Self> {
         let songs = client.command(commands::Queue).await?;
 
         Ok(Self {
             index: Index::new(songs.len()),
-            songs,
+            inner: Queue { songs, cells: Cells::default() },
         })
     }
 
-    pub async fn play(&self, client: &Client, index: usize) {
-        let song = self.songs.get(index).unwrap().id;
-        client.command(commands::Play::song(song)).await.unwrap();
+    pub fn update(&mut self, fresh: Self) {
+        // the same song is queued under a new id each time, so this follows
+        // the one entry even when it is queued twice
+        let selected = self.inner.songs.get(self.index.inner).map(|song| song.id);
+        self.inner.songs = fresh.inner.songs;
+        self.inner.cells.clear();
+        self.index.anchor(self.inner.songs.len(), self.inner.songs.iter().position(|song| Some(song.id) == selected));
+    }
+
+    pub async fn play(&self, client: &Client, index: usize) -> Result<()> {
+        if let Some(song) = self.inner.songs.get(index) {
+            client.command(commands::Play::song(song.id)).await?;
+        }
+
+        Ok(())
+    }
+
+    // position of the song the playbar shows, if it is in this queue
+    pub fn playing_index(&self, state: &State) -> Option<usize> {
+        let current = state.chunks.bottom.inner.playbar.inner.song.as_ref()?;
+        self.inner.songs.iter().position(|song| song.id == current.id)
     }
 }
 
-impl<B: Backend> Render<B> for Queue {
-    fn render(&self, f: &mut Frame<B>, state: &State<B>, layout_chunk: Rect) {
+impl<B: Backend + Send> Render<B> for IndexedBlock<Queue> {
+    fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
         let highlight_state = (
-            state.blocks.is_active(Blokka::Main),
-            state.blocks.is_hovered(Blokka::Main)
+            state.chunks.is_active(BlockKind::Centre),
+            state.chunks.is_hovered(BlockKind::Centre)
         );
 
-        let items = self.songs
-            .iter()
-            .map(|song| { 
-                let artists = song.song.artists();
-                let artist = if artists.len() > 0 {
-                    artists[0].to_string()
-                } else {
-                    String::new()
-                };         
+        let playing_index = self.playing_index(state);
+        let format = |song: &SongInQueue| {
+            let artists = song.song.artists();
+            let artist = if !artists.is_empty() {
+                artists[0].to_string()
+            } else {
+                String::new()
+            };
 
-                //if title is empty, take file name
+            //if title is empty, take file name
 
-                vec![
-                    song.position.0.to_string(), 
-                    song.song.title().unwrap_or("none").to_string(), 
-                    artist,
-                    song.song.duration.unwrap_or(std::time::Duration::from_secs(1)).as_secs().to_string()
-                ]
-            }).collect::<Vec<Vec<String>>>();
+            vec![
+                song.position.0.to_string(),
+                song.song.title().unwrap_or("none").to_string(),
+                artist,
+                song.song.duration.unwrap_or(std::time::Duration::from_secs(1)).as_secs().to_string()
+            ]
+        };
 
 
         let header =  vec![
             TableHeaderItem { text: "#", width: 3 },
-            TableHeaderItem { text: "Title", width: get_percentage_width(layout_chunk.width, 2.0 / 5.0) - 5 },
+            TableHeaderItem { text: "Title", width: get_percentage_width(layout_chunk.width, 2.0 / 5.0).saturating_sub(5) },
             TableHeaderItem { text: "Artist", width: get_percentage_width(layout_chunk.width, 2.0 / 5.0) },
             // TableHeaderItem { text: "Album", width: get_percentage_width(layout_chunk.width, 2.0 / 5.0) },
             TableHeaderItem { text: "Length", width: get_percentage_width(layout_chunk.width, 1.0 / 5.0) },
@@ -66,38 +88,73 @@ impl<B: Backend> Render<B> for Queue {
             layout_chunk,
             " Queue ",
             &header,
-            items,
-            self.index.inner,
+            &self.inner.songs,
+            &self.inner.cells,
+            format,
+            &self.index,
+            playing_index,
             highlight_state,
         )
     }
 }
 
-impl SelectableList for Queue {
-    fn index(&mut self) -> &mut Index {
-        &mut self.index
-    }
-}
+// changes made here reach the view through mpd's queue idle event, only the
+// cursor is moved locally so that it follows the song being moved
+#[async_trait]
+impl BlockTrait for IndexedBlock<Queue> {
+    async fn active_event(state: &mut State, cmd: Command) -> Result<()> {
+        let queue = match &state.chunks.centre.inner.main {
+            MainBlock::Queue(queue) => queue,
+            _ => return Ok(()),
+        };
+
+        let index = queue.index.inner;
+        let playing = queue.playing_index(state);
+        let selected = match queue.inner.songs.get(index) {
+            Some(song) => song.clone(),
+            None => return Ok(()),
+        };
+
+        match cmd {
+            Command::Select => queue.play(&state.client, index).await?,
+
+            Command::MoveItemUp if index > 0 => {
+                state.client.move_in_queue(selected.id, index - 1).await?;
+                state.chunks.centre.inner.main.index().dec();
+            },
+
+            Command::MoveItemDown if index + 1 < queue.inner.songs.len() => {
+                state.client.move_in_queue(selected.id, index + 1).await?;
+                state.chunks.centre.inner.main.index().inc();
+            },
+
+            // moves the selected song right after the current one so it plays next
+            Command::PlayNext => {
+                if let Some(playing) = playing {
+                    let to = if index < playing { playing } else { playing + 1 };
+                    if index != playing {
+                        state.client.move_in_queue(selected.id, to).await?;
+                        state.chunks.centre.inner.main.index().select(to);
+                    }
+                }
+            },
+
+            Command::JumpToPlaying => {
+                if let Some(playing) = playing {
+                    state.chunks.centre.inner.main.index().select(playing);
+                }
+            },
+
+            Command::AddToPlaylist(None) => {
+                let picker = IndexedBlock::<PlaylistPicker>::new(&state.client, selected.song.url).await?;
+                state.chunks.popup = Some(Popup::PlaylistPicker(picker));
+            },
 
-use crate::client::StrofaClient;
-use crate::event::Key;
-impl Queue {
-    pub async fn active_key_event<B>(&self, state: &mut State<B>, key: Key) where B: Backend {
-        match key {
-            Key::Enter => self.play(&state.client, self.index.inner).await,
-            Key::Char('c') => state.client.clear_queue().await.unwrap(),
-            // Key::Char('p') => self.client.proritise_song_in_queue(x.index.inner)
-            // Key::Char('w') => self.client.move_song_up_in_queue(x.songs.get(x.index.inner).unwrap()).await
-            // Key::Char('s') => self.client.move_song_down_in_queue(x.songs.get(x.index.inner).unwrap()).await
-            // Key::Char('A') => self.client.add_song_to_playlist(x.songs.get(x.index.inner).unwrap()).await
-            // Key::Char('o') => x.jump_to_current_song().await
             _ => {}
         }
-    }
 
-    // pub async fn hover_key_event<B>(&self, state: &mut State<B>, key: Key) where B: Backend {
-    //     match key {
+        Ok(())
+    }
 
-    //     }
-    // }
-}
\ No newline at end of file
+    async fn hovered_event(_state: &mut State, _cmd: Command) -> Result<()> { Ok(()) }
+}
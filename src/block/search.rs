@@ -1,167 +1,787 @@
 use anyhow::Result;
 use crate::{ Render, State };
-use super::{ Blokka, TableHeaderItem, SelectableList, Index };
-use super::{ get_color, get_percentage_width, selectable_table };
-use mpd_client::{ Client, commands::{ self, responses::Song } };
-use crate::client::StrofaClient;
-use tui::{ 
-    Frame, backend::Backend, layout::Rect, style::Style, text::{ Span, Text }, 
-    widgets::{ Block, Borders, BorderType, ListItem, Paragraph } 
+use super::{ StandardBlock, IndexedBlock, BlockTrait, MainBlock, TableHeaderItem, Index, Cells };
+use super::{ Artists, Albums, Album, AlbumKind, TrackKind };
+use super::{ get_color, get_percentage_width, selectable_list, selectable_table, list_row, table_row };
+use super::playbar::timestamp;
+use mpd_client::{ Client, commands, commands::responses::Song };
+use crate::client::{ StrofaClient, album_artist };
+use crate::chunk::{ BlockKind, Chunks, Step };
+use crate::history::{ View, Location };
+use crate::event::{ Event, Key, key };
+use crate::command::Command;
+use crate::config::xdg_dir;
+use crate::query::{ self, Query };
+use crate::index::LibraryIndex;
+use crate::collate;
+use std::sync::Arc;
+use async_trait::async_trait;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tui::{
+    Frame, backend::Backend, layout::{ Constraint, Direction, Layout, Rect }, style::Style, text::{ Span, Text },
+    widgets::{ Block, Borders, BorderType, ListItem, Paragraph }
 };
 
+const DEBOUNCE: Duration = Duration::from_millis(200);
+const MAX_HISTORY: usize = 100;
+const HISTORY: &str = "search_history";
+
 #[derive(Default)]
 pub struct Search {
-    pub cursor_position: u16,
     pub query: String,
+    // in characters rather than bytes
+    pub cursor: usize,
+    // why the last query could not be searched for, shown until it is edited
+    pub error: Option<String>,
+    // submitted queries, oldest first
+    pub history: Vec<String>,
+    // the history entry being shown, and what was typed before going through them
+    recalled: Option<(usize, String)>,
+    // bumped with every edit, so results for an older query can be told apart
+    generation: u64,
+    pending: Option<JoinHandle<()>>,
+    // whether the pending search was submitted, its results then being
+    // visited and focused rather than updated in place
+    submitted: bool,
+}
+
+impl Search {
+    fn len(&self) -> usize {
+        self.query.chars().count()
+    }
+
+    fn byte_index(&self, cursor: usize) -> usize {
+        self.query.char_indices().nth(cursor).map_or(self.query.len(), |(i, _)| i)
+    }
+
+    fn insert(&mut self, c: char) {
+        let i = self.byte_index(self.cursor);
+        self.query.insert(i, c);
+        self.cursor += 1;
+    }
+
+    fn backspace(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+
+        self.cursor -= 1;
+        let i = self.byte_index(self.cursor);
+        self.query.remove(i);
+        true
+    }
+
+    // removes the word before the cursor along with the whitespace after it
+    fn delete_word(&mut self) -> bool {
+        let chars: Vec<char> = self.query.chars().collect();
+        let mut start = self.cursor;
+
+        while start > 0 && chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+
+        while start > 0 && !chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+
+        if start == self.cursor {
+            return false;
+        }
+
+        let range = self.byte_index(start)..self.byte_index(self.cursor);
+        self.query.replace_range(range, "");
+        self.cursor = start;
+        true
+    }
+
+    fn clear(&mut self) -> bool {
+        let i = self.byte_index(self.cursor);
+        self.query.replace_range(..i, "");
+        std::mem::replace(&mut self.cursor, 0) > 0
+    }
+
+    fn set_query(&mut self, query: String) {
+        self.cursor = query.chars().count();
+        self.query = query;
+    }
+
+    fn recall_previous(&mut self) -> bool {
+        let i = match &self.recalled {
+            Some((0, _)) => return false,
+            Some((i, _)) => i - 1,
+            None if self.history.is_empty() => return false,
+            None => self.history.len() - 1,
+        };
+
+        let draft = match self.recalled.take() {
+            Some((_, draft)) => draft,
+            None => self.query.clone(),
+        };
+
+        self.set_query(self.history[i].clone());
+        self.recalled = Some((i, draft));
+        true
+    }
+
+    fn recall_next(&mut self) -> bool {
+        match self.recalled.take() {
+            Some((i, draft)) if i + 1 == self.history.len() => self.set_query(draft),
+            Some((i, draft)) => {
+                self.set_query(self.history[i + 1].clone());
+                self.recalled = Some((i + 1, draft));
+            },
+
+            None => return false,
+        }
+
+        true
+    }
+
+    fn remember(&mut self, query: &str) {
+        self.recalled = None;
+        remember(HISTORY, &mut self.history, query);
+    }
+
+    fn cancel(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            pending.abort();
+        }
+
+        self.generation += 1;
+        self.submitted = false;
+    }
+
+    // searches once typing pauses, replacing a search still waiting or running
+    fn schedule(&mut self, playlists: Vec<String>, client: &Client, library: &Arc<LibraryIndex>, events: &UnboundedSender<Event>) {
+        self.cancel();
+
+        if self.query.trim().is_empty() {
+            return;
+        }
+
+        self.spawn(DEBOUNCE, playlists, client, library, events);
+    }
+
+    // `playlists` are the names the playlists block has, which is kept up to
+    // date by mpd's notifications
+    fn spawn(&mut self, delay: Duration, playlists: Vec<String>, client: &Client, library: &Arc<LibraryIndex>, events: &UnboundedSender<Event>) {
+        let (generation, query) = (self.generation, self.query.clone());
+        let (client, library, events) = (client.clone(), library.clone(), events.clone());
+
+        self.pending = Some(tokio::spawn(async move {
+            time::sleep(delay).await;
+            let found = SearchResults::fetch(&query, playlists, &client, &library).await;
+            // only fails once the event loop is gone, when nobody is left to show them to
+            let _ = events.send(Event::SearchResults(generation, query, found));
+        }));
+    }
+}
+
+fn history_path(name: &str) -> Option<PathBuf> {
+    Some(xdg_dir("XDG_STATE_HOME", ".local/state")?.join(name))
+}
+
+// the history is a convenience, so failing to read or write it is not worth
+// interrupting anything for
+pub(super) fn load_history(name: &str) -> Vec<String> {
+    history_path(name)
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|history| history.lines().filter(|line| !line.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+fn save_history(name: &str, history: &[String]) {
+    if let Some(path) = history_path(name) {
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+
+        let _ = std::fs::write(path, history.join("\n") + "\n");
+    }
+}
+
+// moves `entry` to the end of the history, most recent last, and saves it
+pub(super) fn remember(name: &str, history: &mut Vec<String>, entry: &str) {
+    push_entry(history, entry);
+    save_history(name, history);
+}
+
+fn push_entry(history: &mut Vec<String>, entry: &str) {
+    history.retain(|previous| previous != entry);
+    history.push(entry.to_string());
+
+    if history.len() > MAX_HISTORY {
+        history.remove(0);
+    }
+}
+
+impl StandardBlock<Search> {
+    pub fn new() -> Self {
+        Self {
+            inner: Search {
+                history: load_history(HISTORY),
+                ..Search::default()
+            }
+        }
+    }
+
+    // searches for `query` in the background, showing the results once they
+    // arrive as if it had been typed
+    pub fn submit(state: &mut State, query: String) {
+        let search = &mut state.chunks.top.inner.search.inner;
+        search.cancel();
+        search.remember(&query);
+        search.set_query(query.clone());
+
+        // the live results may already be showing this query
+        if let MainBlock::SearchResults(x) = &state.chunks.centre.inner.main {
+            if x.inner.query == query {
+                state.chunks.set_active(BlockKind::Centre);
+                return;
+            }
+        }
+
+        let playlists = state.chunks.centre.inner.left_chunk.inner.bottom.names();
+        let search = &mut state.chunks.top.inner.search.inner;
+        search.submitted = true;
+        search.spawn(Duration::ZERO, playlists, &state.client, &state.library, &state.events);
+    }
+
+    // keys bound to nothing while typing a query, characters are typed into it
+    pub fn input(state: &mut State, key: Key) {
+        if let key!(Char(c)) = key {
+            state.chunks.top.inner.search.inner.insert(c);
+            StandardBlock::<Search>::edited(state);
+        }
+    }
+
+    // searches again as the query changes
+    fn edited(state: &mut State) {
+        let playlists = state.chunks.centre.inner.left_chunk.inner.bottom.names();
+        let search = &mut state.chunks.top.inner.search.inner;
+        search.error = None;
+        search.schedule(playlists, &state.client, &state.library, &state.events);
+    }
+
+    // live results, dropped when the query has been edited since they were asked for
+    pub fn show_results(state: &mut State, generation: u64, query: String, found: Result<Found>) {
+        let search = &mut state.chunks.top.inner.search.inner;
+        if generation != search.generation {
+            return;
+        }
+
+        search.pending = None;
+        let submitted = std::mem::take(&mut search.submitted);
+
+        match found {
+            Ok(found) => {
+                search.error = None;
+                // typing a query moves on from any view still loading
+                state.chunks.cancel_load();
+                let results = SearchResults::from_found(query, found);
+                if submitted {
+                    state.chunks.set_main(MainBlock::SearchResults(StandardBlock { inner: results }));
+                    return;
+                }
+
+                // refining a query updates the results in place instead of
+                // leaving a step in the history for every key typed
+                let results = MainBlock::SearchResults(StandardBlock { inner: results });
+                match &state.chunks.centre.inner.main {
+                    MainBlock::SearchResults(_) => state.chunks.update_main(results),
+                    _ => state.chunks.push_main(results),
+                }
+            },
+
+            // bad queries and mpd rejecting them are both shown in the block
+            Err(e) => search.error = Some(e.to_string()),
+        }
+    }
 }
 
-impl<B: Backend> Render<B> for Search {
-    fn render(&self, f: &mut Frame<B>, state: &State<B>, layout_chunk: Rect) {
+impl<B: Backend + Send> Render<B> for StandardBlock<Search> {
+    fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
         let highlight_state = (
-            state.blocks.is_active(Blokka::Search),
-            state.blocks.is_hovered(Blokka::Search)
+            state.chunks.is_active(BlockKind::TopLeft),
+            state.chunks.is_hovered(BlockKind::TopLeft)
         );
 
-        let lines = Text::from((&self.query).as_str());
-        let search = Paragraph::new(lines).block(
+        let (title, colour) = match &self.inner.error {
+            Some(error) => (format!(" Search ───┤ {} ├", error), Style::default().fg(state.theme.error_border)),
+            None => (String::from(" Search "), get_color(highlight_state, state.theme)),
+        };
+
+        // keeps the cursor in view when the query is wider than the block
+        let width = layout_chunk.width.saturating_sub(2) as usize;
+        let scroll = (self.inner.cursor + 1).saturating_sub(width);
+
+        let lines = Text::from(self.inner.query.as_str());
+        let search = Paragraph::new(lines).scroll((0, scroll as u16)).block(
             Block::default()
                 .borders(Borders::ALL)
-                .title(Span::styled(
-                    " Search ",
-                    get_color(highlight_state, state.theme),
-                )).border_style(get_color(highlight_state, state.theme))
+                .title(Span::styled(title, colour))
+                .border_style(colour)
                 .border_type(BorderType::Rounded),
         );
 
         f.render_widget(search, layout_chunk);
-    }    
+
+        if state.chunks.is_active(BlockKind::TopLeft) {
+            f.set_cursor(layout_chunk.x + 1 + (self.inner.cursor - scroll) as u16, layout_chunk.y + 1);
+        }
+    }
 }
 
-use crate::block::MainBlock;
-use crate::event::Key;
-impl Search {
-    pub async fn active_key_event<B>(state: &mut State<B>, key: Key) where B: Backend {
-        match key {
-            Key::Enter => { 
-                let query = state.blocks.search.query.clone();
-                state.blocks.main = MainBlock::SearchResults(SearchResults::new(&state.client, query).await.unwrap());
-                state.blocks.set_active(Blokka::Main);
-                state.blocks.hovered = Blokka::Main;
+#[async_trait]
+impl BlockTrait for StandardBlock<Search> {
+    async fn active_event(state: &mut State, cmd: Command) -> Result<()> {
+        let search = &mut state.chunks.top.inner.search.inner;
+
+        let edited = match cmd {
+            Command::Select if search.query.trim().is_empty() => false,
+            Command::Select => {
+                let query = search.query.clone();
+                StandardBlock::<Search>::submit(state, query);
+                return Ok(());
             },
 
-            Key::Char(c) => {
-                state.blocks.search.query.push(c);
-                state.blocks.search.cursor_position+=1;
-            },
+            Command::DeleteBackward => search.backspace(),
+            Command::DeleteWord => search.delete_word(),
+            Command::ClearLine => search.clear(),
 
-            Key::Backspace => {
-                state.blocks.search.query.pop();
-                state.blocks.search.cursor_position-=1;
-            }
+            Command::MoveLeft => { search.cursor = search.cursor.saturating_sub(1); false },
+            Command::MoveRight => { search.cursor = (search.cursor + 1).min(search.len()); false },
+            Command::JumpToStart => { search.cursor = 0; false },
+            Command::JumpToEnd => { search.cursor = search.len(); false },
 
-            _ => {}
+            Command::MoveUp => search.recall_previous(),
+            Command::MoveDown => search.recall_next(),
+
+            // leaving the search drops results still on their way, which
+            // would otherwise replace the view gone back to
+            Command::Cancel => { search.cancel(); false },
+            _ => false,
+        };
+
+        if edited {
+            StandardBlock::<Search>::edited(state);
         }
+
+        Ok(())
     }
 
-    pub async fn hovered_key_event<B>(state: &mut State<B>, key: Key) where B: Backend {
-        match key {
-            Key::Down => {
-                for previous in state.blocks.hover_history.clone().into_iter() {
-                    if previous == Blokka::Library || previous == Blokka::Main {
-                        state.blocks.set_hover(&previous);
-                        return;  
-                    }
-                }
+    async fn hovered_event(_state: &mut State, _cmd: Command) -> Result<()> { Ok(()) }
+}
 
-                state.blocks.set_hover(&Blokka::Library)
-            },
+// results are grouped like spotify's, each pane keeping its own selection
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ResultPane {
+    Tracks,
+    Artists,
+    Albums,
+    Playlists,
+}
 
-            Key::Right => state.blocks.set_hover(&Blokka::Sort),
-            _ => {},
+impl ResultPane {
+    fn next(self) -> Self {
+        match self {
+            ResultPane::Tracks => ResultPane::Artists,
+            ResultPane::Artists => ResultPane::Albums,
+            ResultPane::Albums => ResultPane::Playlists,
+            ResultPane::Playlists => ResultPane::Tracks,
         }
     }
-}
 
+    // tracks and artists share the top row, albums and playlists the bottom one
+    fn beside(self) -> Self {
+        match self {
+            ResultPane::Tracks => ResultPane::Artists,
+            ResultPane::Artists => ResultPane::Tracks,
+            ResultPane::Albums => ResultPane::Playlists,
+            ResultPane::Playlists => ResultPane::Albums,
+        }
+    }
+}
 
+pub struct SearchResults {
+    pub query: String,
+    pub focus: ResultPane,
+    pub tracks: IndexedBlock<Vec<Song>>,
+    pub artists: IndexedBlock<Vec<String>>,
+    pub albums: IndexedBlock<Vec<Album>>,
+    pub playlists: IndexedBlock<Vec<String>>,
+    pub track_cells: Cells,
+    pub album_cells: Cells,
+}
 
+impl<T> IndexedBlock<Vec<T>> {
+    fn from_items(items: Vec<T>) -> Self {
+        Self { index: Index::new(items.len()), inner: items }
+    }
 
+    // keeps the same item selected, as told apart by `key`, if it is still there
+    fn set_items<K: PartialEq>(&mut self, items: Vec<T>, key: impl Fn(&T) -> K) {
+        let selected = self.selected().map(&key);
+        self.index.anchor(items.len(), items.iter().position(|item| selected.as_ref() == Some(&key(item))));
+        self.inner = items;
+    }
 
+    fn selected(&self) -> Option<&T> {
+        self.inner.get(self.index.inner)
+    }
+}
 
+// what a search found, before being split into panes
+pub struct Found {
+    tracks: Vec<Song>,
+    artists: Vec<String>,
+    albums: Vec<Album>,
+    playlists: Vec<String>,
+}
 
+impl SearchResults {
+    async fn fetch(query: &str, playlists: Vec<String>, client: &Client, library: &Arc<LibraryIndex>) -> Result<Found> {
+        let query = query::parse(query)?;
+        let tracks = match &query {
+            // matching the whole library takes a moment, so keep it off the event loop
+            Query::Terms(terms) if library.is_loaded() => {
+                let (library, terms) = (library.clone(), terms.clone());
+                tokio::task::spawn_blocking(move || library.search(&terms)).await?
+            },
 
+            // the index is still being built
+            Query::Terms(_) => client.search(query.filter()).await?,
+            // mpd's own syntax, compared as written
+            Query::Filter(filter) => client.command(commands::Find::new(filter.clone())).await?,
+        };
 
+        // listed under album artists, like the artists view
+        let artists: BTreeSet<&str> = tracks.iter().map(album_artist).filter(|artist| !artist.is_empty()).collect();
+        let mut artists: Vec<String> = artists.into_iter().map(str::to_string).collect();
+        artists.sort_by_cached_key(|artist| collate::key(artist));
+        let albums = Albums::group(tracks.clone());
 
+        let playlists = playlists.into_iter().filter(|name| query.matches(name)).collect();
 
+        Ok(Found { tracks, artists, albums, playlists })
+    }
 
+    // tracks and artists on top, albums and playlists below, the lists taking up less room
+    pub fn panes(area: Rect) -> [(ResultPane, Rect); 4] {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(area);
+
+        let columns = |row: Rect| Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(65), Constraint::Percentage(35)].as_ref())
+            .split(row);
+
+        let (top, bottom) = (columns(rows[0]), columns(rows[1]));
+        [
+            (ResultPane::Tracks, top[0]),
+            (ResultPane::Artists, top[1]),
+            (ResultPane::Albums, bottom[0]),
+            (ResultPane::Playlists, bottom[1]),
+        ]
+    }
 
+    // the row of the focused pane on terminal row `row`
+    pub fn row_at(&self, area: Rect, row: u16) -> Option<usize> {
+        let pane = Self::panes(area).into_iter().find(|(pane, _)| *pane == self.focus)?.1;
+        match self.focus {
+            ResultPane::Tracks => table_row(pane, &self.tracks.index, row),
+            ResultPane::Artists => list_row(pane, &self.artists.index, row),
+            ResultPane::Albums => table_row(pane, &self.albums.index, row),
+            ResultPane::Playlists => list_row(pane, &self.playlists.index, row),
+        }
+    }
 
+    fn from_found(query: String, found: Found) -> Self {
+        Self {
+            query,
+            focus: ResultPane::Tracks,
+            tracks: IndexedBlock::from_items(found.tracks),
+            artists: IndexedBlock::from_items(found.artists),
+            albums: IndexedBlock::from_items(found.albums),
+            playlists: IndexedBlock::from_items(found.playlists),
+            track_cells: Cells::default(),
+            album_cells: Cells::default(),
+        }
+    }
 
+    pub fn index(&mut self) -> &mut Index {
+        match self.focus {
+            ResultPane::Tracks => &mut self.tracks.index,
+            ResultPane::Artists => &mut self.artists.index,
+            ResultPane::Albums => &mut self.albums.index,
+            ResultPane::Playlists => &mut self.playlists.index,
+        }
+    }
 
+    pub fn focused_index(&self) -> &Index {
+        match self.focus {
+            ResultPane::Tracks => &self.tracks.index,
+            ResultPane::Artists => &self.artists.index,
+            ResultPane::Albums => &self.albums.index,
+            ResultPane::Playlists => &self.playlists.index,
+        }
+    }
 
+    // the songs behind the selection in the focused pane
+    pub fn selected_album(&self) -> Option<(String, String)> {
+        match self.focus {
+            ResultPane::Tracks => self.tracks.selected().map(|song| (album_artist(song).to_string(), song.album().unwrap_or_default().to_string())),
+            ResultPane::Albums => self.albums.selected().map(|album| (album.artist.clone(), album.name.clone())),
+            ResultPane::Artists | ResultPane::Playlists => None,
+        }
+    }
 
+    pub async fn selected_uris(&self, client: &Client) -> Result<Vec<String>> {
+        Ok(match self.focus {
+            ResultPane::Tracks => self.tracks.selected().map(|song| vec![song.url.clone()]).unwrap_or_default(),
+            ResultPane::Artists => match self.artists.selected() {
+                Some(artist) => Artists::songs(client, artist).await?.into_iter().map(|song| song.url).collect(),
+                None => Vec::new(),
+            },
 
+            ResultPane::Albums => self.albums.selected().map(|album| album.uris.clone()).unwrap_or_default(),
+            ResultPane::Playlists => match self.playlists.selected() {
+                Some(name) => client.command(commands::GetPlaylist(name.to_string())).await?.into_iter().map(|song| song.url).collect(),
+                None => Vec::new(),
+            },
+        })
+    }
+}
 
+impl StandardBlock<SearchResults> {
+    // restored away from the playlists block, so asks mpd for the playlists
+    pub async fn new(query: String, client: &Client, library: &Arc<LibraryIndex>) -> Result<Self> {
+        let playlists = client.command(commands::GetPlaylists).await?.into_iter().map(|playlist| playlist.name).collect();
+        let found = SearchResults::fetch(&query, playlists, client, library).await?;
+        Ok(Self { inner: SearchResults::from_found(query, found) })
+    }
 
-pub struct SearchResults {
-    pub index: Index,
-    pub songs: Vec<Song>
+    // takes the results of the same query searched for again, or of the
+    // query refined, keeping the pane focused and what is selected in each
+    pub fn update(&mut self, fresh: Self) {
+        self.inner.query = fresh.inner.query;
+        self.inner.tracks.set_items(fresh.inner.tracks.inner, |song| song.url.clone());
+        self.inner.artists.set_items(fresh.inner.artists.inner, String::clone);
+        self.inner.albums.set_items(fresh.inner.albums.inner, |album| (album.name.clone(), album.artist.clone()));
+        self.inner.playlists.set_items(fresh.inner.playlists.inner, String::clone);
+        self.inner.track_cells.clear();
+        self.inner.album_cells.clear();
+    }
 }
 
-impl SelectableList for SearchResults {
-    fn index(&mut self) -> &mut Index {
-        &mut self.index
-    }
+// what enter does depends on the pane, so work out the target before borrowing state mutably
+enum Selection {
+    Track(String),
+    Artist(String),
+    Album(String, String),
+    Playlist(String),
 }
 
-impl SearchResults {
-    pub async fn new(client: &Client, query: String) -> Result<Self> {
-        Ok(Self {
-            index: Index::new(50),
-            songs: client.search(&query).await?
-        })
+#[async_trait]
+impl BlockTrait for StandardBlock<SearchResults> {
+    async fn active_event(state: &mut State, cmd: Command) -> Result<()> {
+        let results = match &mut state.chunks.centre.inner.main {
+            MainBlock::SearchResults(results) => &mut results.inner,
+            _ => return Ok(()),
+        };
+
+        let selection = match results.focus {
+            ResultPane::Tracks => results.tracks.selected().map(|song| Selection::Track(song.url.clone())),
+            ResultPane::Artists => results.artists.selected().map(|artist| Selection::Artist(artist.clone())),
+            ResultPane::Albums => results.albums.selected().map(|album| Selection::Album(album.artist.clone(), album.name.clone())),
+            ResultPane::Playlists => results.playlists.selected().map(|name| Selection::Playlist(name.clone())),
+        };
+
+        match (cmd, selection) {
+            (Command::NextPane, _) => results.focus = results.focus.next(),
+            (Command::MoveLeft | Command::MoveRight, _) => results.focus = results.focus.beside(),
+
+            (Command::Select, Some(Selection::Track(uri))) => state.client.play_uris(&[uri]).await?,
+            (Command::Select, Some(Selection::Artist(artist))) => Chunks::load(state, Location::new(View::Albums(AlbumKind::Artist(artist))), Step::Visit),
+            (Command::Select, Some(Selection::Album(artist, name))) => Chunks::load(state, Location::new(View::Tracks(TrackKind::Album(artist, name))), Step::Visit),
+            (Command::Select, Some(Selection::Playlist(name))) => Chunks::load(state, Location::new(View::Tracks(TrackKind::Playlist(name))), Step::Visit),
+
+            _ => {}
+        }
+
+        Ok(())
     }
+
+    async fn hovered_event(_state: &mut State, _cmd: Command) -> Result<()> { Ok(()) }
 }
 
-impl<B: Backend> Render<B> for SearchResults {
-    fn render(&self, f: &mut Frame<B>, state: &State<B>, layout_chunk: Rect) {
-        let highlight_state = (
-            state.blocks.is_active(Blokka::Main),
-            state.blocks.is_hovered(Blokka::Main)
+impl<B: Backend + Send> Render<B> for StandardBlock<SearchResults> {
+    fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
+        let results = &self.inner;
+        let highlight_state = |pane: ResultPane| (
+            state.chunks.is_active(BlockKind::Centre) && results.focus == pane,
+            state.chunks.is_hovered(BlockKind::Centre) && results.focus == pane
         );
 
-        let items = self.songs
-            .iter()
-            .map(|song| { 
-                let artists = song.artists();
-                let artist = if artists.len() > 0 {
-                    artists[0].to_string()
-                } else {
-                    String::new()
-                };
-
-                vec![
-                    song.title().unwrap_or("none").to_string(), 
-                    artist,
-                    song.duration.unwrap_or(std::time::Duration::from_secs(1)).as_secs().to_string()
-                ]
-            }).collect::<Vec<Vec<String>>>();
-
-        let header =  vec![
-            TableHeaderItem { text: "Title", width: get_percentage_width(layout_chunk.width, 2.0 / 5.0) - 5 },
-            TableHeaderItem { text: "Artist", width: get_percentage_width(layout_chunk.width, 2.0 / 5.0) },
-            // TableHeaderItem { text: "Album", width: get_percentage_width(layout_chunk.width, 2.0 / 5.0) },
-            TableHeaderItem { text: "Length", width: get_percentage_width(layout_chunk.width, 1.0 / 5.0) },
+        let [(_, tracks_area), (_, artists_area), (_, albums_area), (_, playlists_area)] = SearchResults::panes(layout_chunk);
+
+        let format_track = |song: &Song| {
+            let artists = song.artists();
+            let artist = if !artists.is_empty() {
+                artists[0].to_string()
+            } else {
+                String::new()
+            };
+
+            vec![
+                song.title().unwrap_or("none").to_string(),
+                artist,
+                song.album().unwrap_or_default().to_string(),
+                song.duration.map(timestamp).unwrap_or_default()
+            ]
+        };
+
+        let header = vec![
+            TableHeaderItem { text: "Title", width: get_percentage_width(tracks_area.width, 2.0 / 7.0) },
+            TableHeaderItem { text: "Artist", width: get_percentage_width(tracks_area.width, 2.0 / 7.0) },
+            TableHeaderItem { text: "Album", width: get_percentage_width(tracks_area.width, 2.0 / 7.0) },
+            TableHeaderItem { text: "Length", width: get_percentage_width(tracks_area.width, 1.0 / 7.0) },
         ];
 
+        let breadcrumb = state.chunks.history.breadcrumb(&View::Search(results.query.clone()));
         selectable_table(
             f,
             state,
-            layout_chunk,
-            " Search Results ",
+            tracks_area,
+            &format!("{}───┤ Tracks ├", breadcrumb),
             &header,
-            items,
-            self.index.inner,
-            highlight_state,
-        )
+            &results.tracks.inner,
+            &results.track_cells,
+            format_track,
+            &results.tracks.index,
+            None,
+            highlight_state(ResultPane::Tracks),
+        );
+
+        selectable_list(
+            f,
+            state,
+            artists_area,
+            " Artists ",
+            &results.artists.inner,
+            |artist| ListItem::new(Span::from(artist.as_str())),
+            highlight_state(ResultPane::Artists),
+            &results.artists.index
+        );
+
+        let format_album = |album: &Album| vec![
+            album.year.clone().unwrap_or_default(),
+            album.name.clone(),
+            album.artist.clone(),
+        ];
+
+        let header = vec![
+            TableHeaderItem { text: "Year", width: 5 },
+            TableHeaderItem { text: "Album", width: get_percentage_width(albums_area.width, 1.0 / 2.0) },
+            TableHeaderItem { text: "Artist", width: get_percentage_width(albums_area.width, 1.0 / 2.0).saturating_sub(5) },
+        ];
+
+        selectable_table(
+            f,
+            state,
+            albums_area,
+            " Albums ",
+            &header,
+            &results.albums.inner,
+            &results.album_cells,
+            format_album,
+            &results.albums.index,
+            None,
+            highlight_state(ResultPane::Albums),
+        );
+
+        selectable_list(
+            f,
+            state,
+            playlists_area,
+            " Playlists ",
+            &results.playlists.inner,
+            |name| ListItem::new(Span::from(name.as_str())),
+            highlight_state(ResultPane::Playlists),
+            &results.playlists.index
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(query: &str, cursor: usize) -> Search {
+        Search { query: query.to_string(), cursor, ..Search::default() }
+    }
+
+    #[test]
+    fn editing_at_the_cursor() {
+        let mut search = typed("björk", 3);
+        search.insert('x');
+        assert_eq!((search.query.as_str(), search.cursor), ("bjöxrk", 4));
+
+        assert!(search.backspace());
+        assert!(search.backspace());
+        assert_eq!((search.query.as_str(), search.cursor), ("bjrk", 2));
+
+        let mut search = typed("", 0);
+        assert!(!search.backspace());
+    }
+
+    #[test]
+    fn deleting_words() {
+        let mut search = typed("artist:björk  joga", 14);
+        assert!(search.delete_word());
+        assert_eq!((search.query.as_str(), search.cursor), ("joga", 0));
+        assert!(!search.delete_word());
+
+        let mut search = typed("homo genic", 7);
+        assert!(search.clear());
+        assert_eq!((search.query.as_str(), search.cursor), ("nic", 0));
+    }
+
+    #[test]
+    fn recalling_history() {
+        let mut search = Search { history: vec![String::from("one"), String::from("two")], ..typed("draft", 5) };
+        assert!(search.recall_previous());
+        assert_eq!(search.query, "two");
+        assert!(search.recall_previous());
+        assert_eq!((search.query.as_str(), search.cursor), ("one", 3));
+        assert!(!search.recall_previous());
+
+        assert!(search.recall_next());
+        assert_eq!(search.query, "two");
+        assert!(search.recall_next());
+        assert_eq!(search.query, "draft");
+        assert!(!search.recall_next());
+    }
+
+    #[test]
+    fn history_entries() {
+        let mut history = vec![String::from("one"), String::from("two")];
+        push_entry(&mut history, "one");
+        assert_eq!(history, ["two", "one"]);
+
+        for i in 0..MAX_HISTORY {
+            push_entry(&mut history, &i.to_string());
+        }
+
+        assert_eq!(history.len(), MAX_HISTORY);
+        assert_eq!(history.first().map(String::as_str), Some("0"));
+        assert_eq!(history.last().map(String::as_str), Some((MAX_HISTORY - 1).to_string().as_str()));
     }
 }
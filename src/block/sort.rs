@@ -1,46 +1,186 @@
-use super::{ Blokka, State, Render, Index, get_color };
-use tui::{ 
+use super::{ IndexedBlock, BlockTrait, State, Render, Index, Album, get_color };
+use mpd_client::commands::responses::Song;
+use crate::client::{ release_date, language };
+use crate::collate::{ self, Collated };
+use crate::chunk::{ BlockKind, Chunks };
+use crate::history::View;
+use crate::command::Command;
+use async_trait::async_trait;
+use anyhow::Result;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use tui::{
     Frame,
-    backend::Backend, 
-    layout::{ Rect }, 
-    style::Style, 
-    text::{Span, Text}, 
-    widgets::{ Block, Borders, BorderType, Paragraph } 
+    backend::Backend,
+    layout::{ Rect },
+    style::Style,
+    text::{Span, Text},
+    widgets::{ Block, Borders, BorderType, Paragraph }
 };
 
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SortKey {
+    ReleaseDate,
+    Language,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 2] = [SortKey::ReleaseDate, SortKey::Language];
+
+    fn label(&self) -> &'static str {
+        match self {
+            SortKey::ReleaseDate => "Date of Release",
+            SortKey::Language => "Language",
+        }
+    }
+
+    fn song(&self, song: &Song) -> Option<String> {
+        match self {
+            SortKey::ReleaseDate => release_date(song).map(str::to_string),
+            SortKey::Language => language(song).map(str::to_string),
+        }
+    }
+
+    fn album(&self, album: &Album) -> Option<String> {
+        match self {
+            SortKey::ReleaseDate => album.date.clone(),
+            SortKey::Language => album.language.clone(),
+        }
+    }
+}
+
+// the keys a view is ordered by, most significant first, each ascending or
+// not; whatever they leave tied keeps the view's own order
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct Sorting(pub Vec<(SortKey, bool)>);
+
+// a value of one key chosen; every item shares the direction of each key, so
+// a missing value only ever meets a present one and goes last either way
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum SortValue {
+    Ascending(Collated),
+    Descending(Reverse<Collated>),
+    Missing,
+}
+
+impl Sorting {
+    // picking the primary key again flips its direction, picking another one
+    // makes it primary with the previous keys breaking ties
+    fn choose(&mut self, key: SortKey) {
+        match self.0.first_mut() {
+            Some((primary, ascending)) if *primary == key => *ascending = !*ascending,
+            _ => {
+                self.remove(key);
+                self.0.insert(0, (key, true));
+            },
+        }
+    }
+
+    fn remove(&mut self, key: SortKey) {
+        self.0.retain(|(k, _)| *k != key);
+    }
+
+    fn position(&self, key: SortKey) -> Option<(usize, bool)> {
+        self.0.iter().position(|(k, _)| *k == key).map(|i| (i, self.0[i].1))
+    }
+
+    // worked out once per item rather than on every comparison, which
+    // would collate the same names over and over
+    fn key<T>(&self, item: &T, value: impl Fn(SortKey, &T) -> Option<String>) -> Vec<SortValue> {
+        self.0.iter()
+            .map(|(key, ascending)| match (value(*key, item), ascending) {
+                (Some(value), true) => SortValue::Ascending(collate::key(&value)),
+                (Some(value), false) => SortValue::Descending(Reverse(collate::key(&value))),
+                (None, _) => SortValue::Missing,
+            })
+            .collect()
+    }
+
+    pub fn sort_songs(&self, songs: &mut [Song]) {
+        if !self.0.is_empty() {
+            songs.sort_by_cached_key(|song| self.key(song, |key, song| key.song(song)));
+        }
+    }
+
+    pub fn sort_albums(&self, albums: &mut [Album]) {
+        if !self.0.is_empty() {
+            albums.sort_by_cached_key(|album| self.key(album, |key, album| key.album(album)));
+        }
+    }
+}
+
+// views sharing a slot share their sorting, so every album list is ordered
+// the same way
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SortSlot {
+    Albums,
+    Tracks,
+    Search,
+}
+
+impl SortSlot {
+    // the queue and playlists' order is their own, artists have neither a date nor a language
+    fn of(view: &View) -> Option<Self> {
+        match view {
+            View::Albums(_) => Some(SortSlot::Albums),
+            View::Tracks(kind) if !kind.is_playlist() => Some(SortSlot::Tracks),
+            View::Search(_) => Some(SortSlot::Search),
+            _ => None,
+        }
+    }
+}
+
 pub struct Sort {
-    pub entries: [&'static str; 2],
-    pub asc: bool,
-    pub index: Index 
+    pub orders: HashMap<SortSlot, Sorting>,
 }
 
 impl Sort {
-    pub async fn new() -> Self {
+    pub fn sorting(&self, view: &View) -> Option<&Sorting> {
+        self.orders.get(&SortSlot::of(view)?)
+    }
+}
+
+impl IndexedBlock<Sort> {
+    pub fn new() -> Self {
         Self {
-            entries: [
-                "Date of Release",
-                "Language",
-            ],
-            asc: true,
-            index: Index::new(2),
-        }        
+            index: Index::new(SortKey::ALL.len()),
+            inner: Sort { orders: HashMap::new() },
+        }
     }
 }
 
-impl<B: Backend> Render<B> for Sort {
-    fn render(&self, f: &mut Frame<B>, state: &State<B>, layout_chunk: Rect) {
+impl<B: Backend + Send> Render<B> for IndexedBlock<Sort> {
+    fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
         let highlight_state = (
-            state.blocks.is_active(Blokka::Sort),
-            state.blocks.is_hovered(Blokka::Sort)
+            state.chunks.is_active(BlockKind::TopRight),
+            state.chunks.is_hovered(BlockKind::TopRight)
         );
 
+        let view = state.chunks.centre.inner.main.view();
+        let sorting = self.inner.sorting(&view);
+        let arrow = |ascending: bool| if ascending { "↑" } else { "↓" };
+
+        // browsing the keys shows where the selected one stands, otherwise the primary key is shown
+        let line = match (state.chunks.is_active(BlockKind::TopRight), SortKey::ALL.get(self.index.inner)) {
+            _ if SortSlot::of(&view).is_none() => String::from("Unsortable"),
+            (true, Some(key)) => match sorting.and_then(|sorting| sorting.position(*key)) {
+                Some((i, ascending)) => format!("{} {}{}", key.label(), arrow(ascending), i + 1),
+                None => format!("{} -", key.label()),
+            },
+
+            _ => match sorting.and_then(|sorting| sorting.0.first()) {
+                Some((key, ascending)) => format!("{} {}", key.label(), arrow(*ascending)),
+                None => String::from("Default"),
+            },
+        };
+
         let block = Block::default()
             .title(Span::styled(" Sort By ", Style::default().fg(state.theme.text)))
             .borders(Borders::ALL)
             .border_style(get_color(highlight_state, state.theme))
             .border_type(BorderType::Rounded);
 
-        let lines = Text::from(self.entries[0]);
+        let lines = Text::from(line);
         let sort = Paragraph::new(lines)
             .block(block)
             .style(get_color(highlight_state, state.theme));
@@ -49,15 +189,35 @@ impl<B: Backend> Render<B> for Sort {
     }
 }
 
-use crate::event::Key;
-impl Sort {
-    pub async fn active_key_event<B>(state: &mut State<B>, key: Key) where B: Backend {}
+#[async_trait]
+impl BlockTrait for IndexedBlock<Sort> {
+    // selecting picks the selected key, or flips it when already primary
+    async fn active_event(state: &mut State, cmd: Command) -> Result<()> {
+        let sort = &mut state.chunks.top.inner.sort;
 
-    pub async fn hovered_key_event<B>(state: &mut State<B>, key: Key) where B: Backend {
-        match key {
-            Key::Left => state.blocks.set_hover(&Blokka::Search),
-            Key::Down => state.blocks.set_hover(&Blokka::Main),
-            _ => {},
+        match cmd {
+            Command::Select | Command::DropSortKey => {
+                let view = state.chunks.centre.inner.main.view();
+                let (slot, selected) = match (SortSlot::of(&view), SortKey::ALL.get(sort.index.inner)) {
+                    (Some(slot), Some(selected)) => (slot, *selected),
+                    _ => return Ok(()),
+                };
+
+                let sorting = sort.inner.orders.entry(slot).or_default();
+                match cmd {
+                    Command::Select => sorting.choose(selected),
+                    _ => sorting.remove(selected),
+                }
+
+                // fetched again for ties to fall back to the view's own order
+                Chunks::reload(state);
+            },
+
+            _ => {}
         }
+
+        Ok(())
     }
-}
\ No newline at end of file
+
+    async fn hovered_event(_state: &mut State, _cmd: Command) -> Result<()> { Ok(()) }
+}
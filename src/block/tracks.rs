@@ -1,72 +1,128 @@
-use super::{ Blokka, State, Render, TableHeaderItem, SelectableList, Index, selectable_table, get_percentage_width };
-use mpd_client::{ Client, commands, commands::responses::Song };
+use super::{ IndexedBlock, BlockTrait, MainBlock, Artists, State, Render, TableHeaderItem, Index, Cells, selectable_table, get_percentage_width };
+use mpd_client::{ Client, commands, commands::responses::Song, filter::Filter, tag::Tag };
+use crate::client::{ StrofaClient, Progress, album_artist };
 use tui::{ backend::Backend, layout::Rect, Frame };
+use crate::chunk::BlockKind;
+use crate::history::View;
+use crate::index::LibraryIndex;
+use std::sync::Arc;
+use crate::command::Command;
+use async_trait::async_trait;
 use anyhow::Result;
 
 pub struct Tracks {
-    pub index: Index,
-    pub kind: String,
+    pub kind: TrackKind,
     pub tracks: Vec<Song>,
+    pub cells: Cells,
 }
 
+#[derive(Clone, PartialEq, Debug)]
 pub enum TrackKind {
-    Album(String),
+    // album artist and album
+    Album(String, String),
     Artist(String),
     Playlist(String),
     All,
 }
 
+impl TrackKind {
+    pub fn is_playlist(&self) -> bool {
+        matches!(self, TrackKind::Playlist(_))
+    }
+}
+
 impl Tracks {
-    pub async fn new(kind: TrackKind, client: &Client) -> Result<Self> {
-        let tracks: Vec<Song> = match kind {
-            TrackKind::Playlist(ref name) => client.command(commands::GetPlaylist(name.to_string())).await?,
-             _ => Vec::new(),
-        };
+    // every song comes out of the library index once it is loaded, rather than listing them all again
+    async fn fetch(kind: &TrackKind, client: &Client, library: &Arc<LibraryIndex>, progress: &Progress<'_>) -> Result<Vec<Song>> {
+        Ok(match kind {
+            TrackKind::Playlist(name) => client.command(commands::GetPlaylist(name.to_string())).await?,
+            TrackKind::Artist(artist) => Artists::songs(client, artist).await?,
+            TrackKind::Album(artist, album) => {
+                // filtering on the album artist here would miss songs filed under their artist
+                let filter = Filter::tag(Tag::Album, album.to_string());
+                let mut songs = client.command(commands::Find::new(filter)).await?;
+                songs.retain(|song| album_artist(song) == artist);
+                songs.sort_by_key(|song| song.number());
+                songs
+            },
+
+            TrackKind::All if library.is_loaded() => library.songs().cloned().collect(),
+            TrackKind::All => client.list_library(progress).await?,
+        })
+    }
+}
+
+impl IndexedBlock<Tracks> {
+    pub async fn new(kind: TrackKind, client: &Client, library: &Arc<LibraryIndex>, progress: &Progress<'_>) -> Result<Self> {
+        let tracks = Tracks::fetch(&kind, client, library, progress).await?;
 
         Ok(Self {
-            kind: kind.to_string(),
             index: Index::new(tracks.len()),
-            tracks,
+            inner: Tracks { kind, tracks, cells: Cells::default() },
         })
     }
 
-    pub async fn play(&self, client: &Client, index: usize) {
-        // let song = self.tracks.get(index).unwrap();
-        // client.command(commands::Play::song(song)).await.unwrap();
+    // takes the songs of the same view fetched again
+    pub fn update(&mut self, fresh: Self) {
+        let selected = self.selected().map(|song| song.url.clone());
+        self.inner.tracks = fresh.inner.tracks;
+        self.inner.cells.clear();
+        self.index.anchor(self.inner.tracks.len(), self.inner.tracks.iter().position(|song| Some(&song.url) == selected.as_ref()));
+    }
+
+    pub fn selected(&self) -> Option<&Song> {
+        self.inner.tracks.get(self.index.inner)
+    }
+}
+
+#[async_trait]
+impl BlockTrait for IndexedBlock<Tracks> {
+    async fn active_event(state: &mut State, cmd: Command) -> Result<()> {
+        let uri = match &state.chunks.centre.inner.main {
+            MainBlock::Tracks(tracks) => tracks.selected().map(|song| song.url.clone()),
+            _ => None,
+        };
+
+        match (cmd, uri) {
+            (Command::Select, Some(uri)) => state.client.play_uris(&[uri]).await?,
+            _ => {}
+        }
+
+        Ok(())
     }
+
+    async fn hovered_event(_state: &mut State, _cmd: Command) -> Result<()> { Ok(()) }
 }
 
-impl<B: Backend> Render<B> for Tracks {
-    fn render(&self, f: &mut Frame<B>, state: &State<B>, layout_chunk: Rect) {
+impl<B: Backend + Send> Render<B> for IndexedBlock<Tracks> {
+    fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
         let highlight_state = (
-            state.blocks.is_active(Blokka::Main),
-            state.blocks.is_hovered(Blokka::Main)
+            state.chunks.is_active(BlockKind::Centre),
+            state.chunks.is_hovered(BlockKind::Centre)
         );
 
-        let items = self.tracks
-            .iter()
-            .map(|track| { 
-                let artists = track.artists();
-                let artist = if artists.len() > 0 {
-                    artists[0].to_string()
-                } else {
-                    String::new()
-                };         
+        let format = |track: &Song| {
+            let artists = track.artists();
+            let artist = if !artists.is_empty() {
+                artists[0].to_string()
+            } else {
+                String::new()
+            };
 
-                //if title is empty, take file name
+            //if title is empty, take file name
 
-                vec![
-                    String::from("0"),// track.position.0.to_string(), 
-                    track.title().unwrap_or("none").to_string(), 
-                    artist,
-                    track.duration.unwrap_or(std::time::Duration::from_secs(1)).as_secs().to_string()
-                ]
-            }).collect::<Vec<Vec<String>>>();
+            vec![
+                track.number().1.to_string(),
+                track.title().unwrap_or("none").to_string(),
+                artist,
+                track.duration.unwrap_or(std::time::Duration::from_secs(1)).as_secs().to_string()
+            ]
+        };
 
 
         let header =  vec![
             TableHeaderItem { text: "#", width: 3 },
-            TableHeaderItem { text: "Title", width: get_percentage_width(layout_chunk.width, 2.0 / 5.0) - 5 },
+            TableHeaderItem { text: "Title", width: get_percentage_width(layout_chunk.width, 2.0 / 5.0).saturating_sub(5) },
             TableHeaderItem { text: "Artist", width: get_percentage_width(layout_chunk.width, 2.0 / 5.0) },
             // TableHeaderItem { text: "Album", width: get_percentage_width(layout_chunk.width, 2.0 / 5.0) },
             TableHeaderItem { text: "Length", width: get_percentage_width(layout_chunk.width, 1.0 / 5.0) },
@@ -76,28 +132,14 @@ impl<B: Backend> Render<B> for Tracks {
             f,
             state,
             layout_chunk,
-            &self.kind,
+            &state.chunks.history.breadcrumb(&View::Tracks(self.inner.kind.clone())),
             &header,
-            items,
-            self.index.inner,
+            &self.inner.tracks,
+            &self.inner.cells,
+            format,
+            &self.index,
+            None,
             highlight_state,
         )
     }
 }
-
-impl SelectableList for Tracks {
-    fn index(&mut self) -> &mut Index {
-        &mut self.index
-    }
-}
-
-impl std::fmt::Display for TrackKind {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            TrackKind::Album(s) => write!(f, " Album {} ", s),
-            TrackKind::Artist(s) => write!(f, " Artist {} ", s),
-            TrackKind::Playlist(s) => write!(f, " Playlist ───┤ {} ├", s),
-            TrackKind::All => write!(f, " Tracks ")
-        }
-    }
-}
\ No newline at end of file
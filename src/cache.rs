@@ -0,0 +1,237 @@
+use crate::config::xdg_dir;
+use mpd_client::{ commands::responses::{ Response, Song }, raw::Frame, tag::Tag };
+use mpd_protocol::Connection;
+use serde::{ Serialize, Deserialize };
+use std::fmt::Write;
+use std::io::Read;
+use std::path::{ Path, PathBuf };
+use std::time::Duration;
+
+// bumped whenever what is cached changes, so an older cache reads as missing
+const VERSION: u32 = 2;
+
+// the whole library as mpd last listed it, kept between runs so only what
+// changed since needs listing again
+#[derive(Serialize, Deserialize)]
+struct LibraryCache {
+    version: u32,
+    host: String,
+    // when mpd last updated its database, from its stats
+    db_update: u64,
+    songs: Vec<CachedSong>,
+}
+
+// mpd_client's songs can neither be serialized nor built outside of it
+#[derive(Serialize, Deserialize)]
+struct CachedSong {
+    url: String,
+    duration: Option<Duration>,
+    format: Option<String>,
+    last_modified: Option<String>,
+    tags: Vec<(String, Vec<String>)>,
+}
+
+impl From<&Song> for CachedSong {
+    fn from(song: &Song) -> Self {
+        Self {
+            url: song.url.clone(),
+            duration: song.duration,
+            format: song.format.clone(),
+            last_modified: song.last_modified.map(|time| time.to_rfc3339()),
+            tags: song.tags.iter()
+                .filter_map(|(tag, values)| Some((tag_name(tag)?.to_string(), values.clone())))
+                .collect(),
+        }
+    }
+}
+
+// songs read back out of the cache
+pub enum Cached {
+    // mpd's database hasn't been updated since they were written
+    Current(Vec<Song>),
+    // as of the update at the time given, to be brought up to date
+    Stale(u64, Vec<Song>),
+}
+
+// tags by the names mpd gives them, which mpd_client keeps to itself; tags
+// missing here are left out of the cache
+fn tag_name(tag: &Tag) -> Option<&str> {
+    Some(match tag {
+        Tag::Album => "Album",
+        Tag::AlbumArtist => "AlbumArtist",
+        Tag::AlbumArtistSort => "AlbumArtistSort",
+        Tag::AlbumSort => "AlbumSort",
+        Tag::Artist => "Artist",
+        Tag::ArtistSort => "ArtistSort",
+        Tag::Comment => "Comment",
+        Tag::Composer => "Composer",
+        Tag::ComposerSort => "ComposerSort",
+        Tag::Conductor => "Conductor",
+        Tag::Date => "Date",
+        Tag::Disc => "Disc",
+        Tag::Ensemble => "Ensemble",
+        Tag::Genre => "Genre",
+        Tag::Grouping => "Grouping",
+        Tag::Label => "Label",
+        Tag::Location => "Location",
+        Tag::Movement => "Movement",
+        Tag::MovementNumber => "MovementNumber",
+        Tag::MusicBrainzArtistId => "MUSICBRAINZ_ARTISTID",
+        Tag::MusicBrainzRecordingId => "MUSICBRAINZ_TRACKID",
+        Tag::MusicBrainzReleaseArtistId => "MUSICBRAINZ_ALBUMARTISTID",
+        Tag::MusicBrainzReleaseId => "MUSICBRAINZ_ALBUMID",
+        Tag::MusicBrainzTrackId => "MUSICBRAINZ_RELEASETRACKID",
+        Tag::MusicBrainzWorkId => "MUSICBRAINZ_WORKID",
+        Tag::Name => "Name",
+        Tag::OriginalDate => "OriginalDate",
+        Tag::Performer => "Performer",
+        Tag::Title => "Title",
+        Tag::Track => "Track",
+        Tag::Work => "Work",
+        Tag::Other(name) => name,
+        _ => return None,
+    })
+}
+
+// songs can only come out of a response from mpd, so the cached ones are
+// written out as mpd would have listed them and read back the same way
+fn rebuild(songs: Vec<CachedSong>) -> Option<Vec<Song>> {
+    let mut response = String::new();
+    for song in songs {
+        let _ = writeln!(response, "file: {}", song.url);
+        if let Some(duration) = song.duration {
+            let _ = writeln!(response, "duration: {}", duration.as_secs_f64());
+        }
+
+        if let Some(format) = song.format {
+            let _ = writeln!(response, "Format: {}", format);
+        }
+
+        if let Some(time) = song.last_modified {
+            let _ = writeln!(response, "Last-Modified: {}", time);
+        }
+
+        for (tag, values) in song.tags {
+            for value in values {
+                let _ = writeln!(response, "{}: {}", tag, value);
+            }
+        }
+    }
+
+    response.push_str("OK\n");
+    parse(response.as_bytes())
+}
+
+// a response as mpd sends it; the greeting is read on its own, as whatever
+// is read along with it is dropped
+pub(crate) fn parse(response: &[u8]) -> Option<Vec<Song>> {
+    let greeting: &[u8] = b"OK MPD 0.23.0\n";
+    let mut connection = Connection::connect(greeting.chain(response)).ok()?;
+    let frame: Frame = connection.receive().ok()??.single_frame().ok()?;
+    Vec::<Song>::from_frame(frame).ok()
+}
+
+// one per server, as each has a library of its own
+fn path(host: &str) -> Option<PathBuf> {
+    let name: String = host.chars().map(|c| if c.is_alphanumeric() || c == '.' || c == '-' { c } else { '_' }).collect();
+    Some(xdg_dir("XDG_CACHE_HOME", ".cache")?.join(format!("library-{}", name)))
+}
+
+// the songs cached for `host`, telling whether mpd's database was updated
+// after they were
+pub fn load(host: &str, db_update: u64) -> Option<Cached> {
+    read(&path(host)?, host, db_update)
+}
+
+fn read(path: &Path, host: &str, db_update: u64) -> Option<Cached> {
+    let bytes = std::fs::read(path).ok()?;
+    let cache: LibraryCache = bincode::deserialize(&bytes).ok()?;
+    if cache.version != VERSION || cache.host != host {
+        return None;
+    }
+
+    let songs = rebuild(cache.songs)?;
+    Some(match cache.db_update == db_update {
+        true => Cached::Current(songs),
+        false => Cached::Stale(cache.db_update, songs),
+    })
+}
+
+// a cache that can't be written only makes the next start slower
+pub fn save(host: &str, db_update: u64, songs: &[Song]) {
+    if let Some(path) = path(host) {
+        write(&path, host, db_update, songs);
+    }
+}
+
+fn write(path: &Path, host: &str, db_update: u64, songs: &[Song]) {
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+
+    let cache = LibraryCache {
+        version: VERSION,
+        host: host.to_string(),
+        db_update,
+        songs: songs.iter().map(CachedSong::from).collect(),
+    };
+
+    // written aside first, so a cache cut short is never read
+    if let Ok(bytes) = bincode::serialize(&cache) {
+        let partial = path.with_extension("partial");
+        if std::fs::write(&partial, bytes).is_ok() {
+            let _ = std::fs::rename(partial, path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LISTING: &str = "file: a/one.flac\n\
+        Last-Modified: 2021-06-01T12:00:00Z\n\
+        Format: 44100:16:2\n\
+        Artist: First\n\
+        Artist: Second\n\
+        AlbumArtistSort: Band, The\n\
+        Title: One\n\
+        Track: 1\n\
+        MUSICBRAINZ_TRACKID: 0f3f\n\
+        duration: 201.587\n\
+        file: b/two.mp3\n\
+        Title: Two\n\
+        OK\n";
+
+    fn cache_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("strofa-test-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn round_trip() {
+        let songs = parse(LISTING.as_bytes()).unwrap();
+        let path = cache_path("round-trip");
+        write(&path, "localhost:6600", 10, &songs);
+
+        let read = read(&path, "localhost:6600", 10);
+        let _ = std::fs::remove_file(&path);
+        match read {
+            Some(Cached::Current(read)) => assert_eq!(read, songs),
+            _ => panic!("the cache wasn't read back"),
+        }
+    }
+
+    #[test]
+    fn stale_update_misses() {
+        let songs = parse(LISTING.as_bytes()).unwrap();
+        let path = cache_path("stale");
+        write(&path, "localhost:6600", 10, &songs);
+
+        let stale = read(&path, "localhost:6600", 11);
+        let other_host = read(&path, "elsewhere:6600", 10);
+        let _ = std::fs::remove_file(&path);
+
+        assert!(matches!(stale, Some(Cached::Stale(10, read)) if read == songs));
+        assert!(other_host.is_none());
+    }
+}
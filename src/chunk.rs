@@ -1,14 +1,26 @@
-use std::marker::PhantomData;
 use tui::layout::{ Direction, Layout, Constraint, Rect };
-use crate::block::{ IndexedBlock, BlockTrait, Library, Playlists };
+use tui::style::{ Modifier, Style };
+use crate::block::{ IndexedBlock, StandardBlock, BlockTrait, MainBlock, Popup, Search, Sort, Library, Playlists, Playbar, Queue, Index, contains, list_row, border_note, spinner, count };
+use mpd_client::commands::{ self, responses::Playlist };
 use tui::backend::Backend;
 use tui::Frame;
 use crate::state::State;
 use crate::Render;
 use anyhow::Result;
-use mpd_client::Client;
+use mpd_client::Subsystem;
 use std::collections::VecDeque;
-use crate::event::Key;
+use crate::event::{ Event, Key, Mouse, MouseKind };
+use crate::command::Command;
+use crate::key::Keymap;
+use crate::config::LayoutConfig;
+use crate::history::{ History, Location };
+use crate::index::LibraryIndex;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+// rows moved by a notch of the mouse wheel
+const SCROLL_LINES: usize = 3;
 
 //move hover events to chunks/blocks
 
@@ -16,12 +28,80 @@ pub struct Chunks {
     pub top: Chunk<Top>,
     pub centre: Chunk<Centre>,
     pub bottom: Chunk<Bottom>,
+    pub popup: Option<Popup>,
     pub active: Option<BlockKind>,
     pub hovered: BlockKind,
     pub hover_history: VecDeque<BlockKind>,
+    pub history: History,
+    pub loading: Option<Loading>,
+    // bumped whenever a load is started or abandoned, so a view asked for
+    // earlier is dropped when it arrives
+    generation: u64,
+    playbar_fetch: Fetch,
+    playlists_fetch: Fetch,
 }
 
-#[derive(Copy, Clone, PartialEq)]
+// a block's contents being fetched again in the background; a newer fetch
+// abandons the one still running, so only the latest is shown
+#[derive(Default)]
+struct Fetch {
+    generation: u64,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Fetch {
+    fn spawn<F>(&mut self, task: impl FnOnce(u64) -> F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+
+        self.generation += 1;
+        self.handle = Some(tokio::spawn(task(self.generation)));
+    }
+
+    // whether what arrived under `generation` is from the latest fetch
+    fn finish(&mut self, generation: u64) -> bool {
+        if generation != self.generation {
+            return false;
+        }
+
+        self.handle = None;
+        true
+    }
+}
+
+// a main view being fetched in the background, shown once it arrives
+pub struct Loading {
+    generation: u64,
+    pub location: Location,
+    step: Step,
+    handle: JoinHandle<()>,
+    // songs listed so far out of the library's, for views listing all of it
+    progress: Option<(usize, usize)>,
+    // the history as it was before stepping back or forward to the view,
+    // put back should the view never be shown
+    previous: Option<History>,
+    // why it couldn't be fetched, shown until another view is asked for
+    pub error: Option<String>,
+}
+
+// what showing a view that has loaded does to the history
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Step {
+    // leaves a step back to the view it replaces, and focuses it
+    Visit,
+    Back,
+    Forward,
+    // in place of the current view, as the queue is when starting
+    Replace,
+    // the current view fetched again, keeping its selection
+    Refresh,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum BlockKind {
     TopLeft,
     TopRight,
@@ -31,22 +111,215 @@ pub enum BlockKind {
     Centre
 }
 
-impl BlockKind {
-    pub fn event<B>(&self, state: &mut State) where B: Backend + Send {
-        
+impl std::fmt::Display for BlockKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BlockKind::TopLeft => write!(f, "top_left"),
+            BlockKind::TopRight => write!(f, "top_right"),
+            BlockKind::LeftTop => write!(f, "left_top"),
+            BlockKind::LeftBottom => write!(f, "left_bottom"),
+            BlockKind::Bottom => write!(f, "bottom"),
+            BlockKind::Centre => write!(f, "centre"),
+        }
+    }
+}
+
+impl std::str::FromStr for BlockKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "top_left" => Ok(BlockKind::TopLeft),
+            "top_right" => Ok(BlockKind::TopRight),
+            "left_top" => Ok(BlockKind::LeftTop),
+            "left_bottom" => Ok(BlockKind::LeftBottom),
+            "bottom" => Ok(BlockKind::Bottom),
+            "centre" => Ok(BlockKind::Centre),
+            _ => Err(format!("unknown block `{}`", s)),
+        }
     }
 }
 
 impl Chunks {
-    pub async fn new(client: &Client) -> Result<Self> {
-        Ok(Self {
-            top: Chunk::<Top>::new().await?,
-            centre: Chunk::<Centre>::new(client).await?,
-            bottom: Chunk::<Bottom>::new().await?,
+    pub fn new(layout: &LayoutConfig) -> Self {
+        Self {
+            top: Chunk::<Top>::new(layout),
+            centre: Chunk::<Centre>::new(layout),
+            bottom: Chunk::<Bottom>::new(),
+            popup: None,
             active: None,
             hovered: BlockKind::LeftTop,
-            hover_history: VecDeque::new()
-        })
+            hover_history: VecDeque::new(),
+            history: History::default(),
+            loading: None,
+            generation: 0,
+            playbar_fetch: Fetch::default(),
+            playlists_fetch: Fetch::default(),
+        }
+    }
+
+    pub fn set_main(&mut self, blk: MainBlock) {
+        self.push_main(blk);
+        self.set_active(BlockKind::Centre);
+    }
+
+    // like `set_main`, but leaves focus where it is
+    pub fn push_main(&mut self, blk: MainBlock) {
+        self.cancel_load();
+        let previous = std::mem::replace(&mut self.centre.inner.main, blk);
+        // reopening the same view is not worth a step back
+        if previous.view() != self.centre.inner.main.view() {
+            self.history.push(previous.location());
+        }
+
+        self.sort_main();
+    }
+
+    // the current view fetched again, keeping its selection; sorted before
+    // the selection is followed into it
+    pub fn update_main(&mut self, mut blk: MainBlock) {
+        if let Some(sorting) = self.top.inner.sort.inner.sorting(&blk.view()) {
+            blk.sort(sorting);
+        }
+
+        self.centre.inner.main.update(blk);
+    }
+
+    // orders the main view as chosen in the sort block, needed whenever its contents are fetched
+    pub fn sort_main(&mut self) {
+        let view = self.centre.inner.main.view();
+        if let Some(sorting) = self.top.inner.sort.inner.sorting(&view) {
+            self.centre.inner.main.sort(sorting);
+        }
+    }
+
+    // fetches a view in the background, abandoning any other still being
+    // fetched, so that moving on from a slow view never waits for it
+    pub fn load(state: &mut State, location: Location, step: Step) {
+        state.chunks.cancel_load();
+        Chunks::spawn_load(state, location, step, None);
+    }
+
+    fn spawn_load(state: &mut State, location: Location, step: Step, previous: Option<History>) {
+        let generation = state.chunks.generation;
+        let (client, library, events) = (state.client.clone(), state.library.clone(), state.events.clone());
+        let restoring = location.clone();
+
+        let handle = tokio::spawn(async move {
+            // sending only fails once the event loop is gone
+            let progress = |done, total| { let _ = events.send(Event::ViewProgress(generation, done, total)); };
+            let blk = MainBlock::restore(&restoring, &client, &library, &progress).await;
+            let _ = events.send(Event::ViewLoaded(generation, blk));
+        });
+
+        state.chunks.loading = Some(Loading { generation, location, step, handle, progress: None, previous, error: None });
+    }
+
+    pub fn view_progress(state: &mut State, generation: u64, done: usize, total: usize) {
+        if let Some(loading) = &mut state.chunks.loading {
+            if loading.generation == generation {
+                loading.progress = Some((done, total));
+            }
+        }
+    }
+
+    // fetches the current view again, or the one on its way should that have
+    // been asked for before whatever changed
+    pub fn reload(state: &mut State) {
+        let (location, step, previous) = match &mut state.chunks.loading {
+            Some(loading) if loading.error.is_none() => (loading.location.clone(), loading.step, loading.previous.take()),
+            _ => (state.chunks.centre.inner.main.location(), Step::Refresh, None),
+        };
+
+        state.chunks.cancel_load();
+        Chunks::spawn_load(state, location, step, previous);
+    }
+
+    // a step back or forward abandoned on the way leaves the history as it was
+    pub fn cancel_load(&mut self) {
+        if let Some(loading) = self.loading.take() {
+            loading.handle.abort();
+            if let Some(previous) = loading.previous {
+                self.history = previous;
+            }
+        }
+
+        self.generation += 1;
+    }
+
+    // a failed fetch leaves the current view as it was, and the history
+    // along with it
+    pub fn view_loaded(state: &mut State, generation: u64, blk: Result<MainBlock>) {
+        let loading = match &mut state.chunks.loading {
+            Some(loading) if loading.generation == generation => loading,
+            _ => return,
+        };
+
+        let blk = match blk {
+            Ok(blk) => blk,
+            Err(e) => {
+                loading.error = Some(e.to_string());
+                if let Some(previous) = loading.previous.take() {
+                    state.chunks.history = previous;
+                }
+
+                return;
+            },
+        };
+
+        let step = loading.step;
+        state.chunks.loading = None;
+
+        match step {
+            Step::Visit => {
+                state.chunks.set_main(blk);
+                return;
+            },
+
+            Step::Refresh => {
+                state.chunks.update_main(blk);
+                return;
+            },
+
+            // the history moved as soon as the step was asked for
+            Step::Back | Step::Forward | Step::Replace => {},
+        }
+
+        state.chunks.centre.inner.main = blk;
+        state.chunks.sort_main();
+    }
+
+    pub fn back(state: &mut State) {
+        Chunks::step(state, Step::Back);
+    }
+
+    pub fn forward(state: &mut State) {
+        Chunks::step(state, Step::Forward);
+    }
+
+    // moves through the history straight away, a step still on its way
+    // counting as taken, so going back twice goes back two views
+    fn step(state: &mut State, step: Step) {
+        let (current, previous) = match &state.chunks.loading {
+            Some(loading) if loading.error.is_none() && loading.previous.is_some() => (loading.location.clone(), loading.previous.clone()),
+            _ => (state.chunks.centre.inner.main.location(), None),
+        };
+
+        let mut history = state.chunks.history.clone();
+        let location = match step {
+            Step::Back => history.back(current),
+            _ => history.forward(current),
+        };
+
+        let location = match location {
+            Some(location) => location,
+            None => return,
+        };
+
+        let previous = previous.unwrap_or_else(|| state.chunks.history.clone());
+        state.chunks.cancel_load();
+        state.chunks.history = history;
+        Chunks::spawn_load(state, location, step, Some(previous));
     }
 
     pub fn set_active(&mut self, blk: BlockKind) {
@@ -56,8 +329,8 @@ impl Chunks {
 
     pub fn set_hover(&mut self, blk: BlockKind) {
         self.hover_history.truncate(5);
-        self.hover_history.push_front(self.hovered.clone());
-        self.hovered = blk.clone();
+        self.hover_history.push_front(self.hovered);
+        self.hovered = blk;
     }
 
     pub fn is_hovered(&self, blk: BlockKind) -> bool {
@@ -68,25 +341,82 @@ impl Chunks {
         if self.active==Some(blk) { return true; } false
     } 
 
-    pub async fn active_event(state: &mut State, key: Key) {
+    // the selection of a block showing a list
+    pub fn index(&mut self, kind: BlockKind) -> Option<&mut Index> {
+        match kind {
+            BlockKind::TopRight => Some(&mut self.top.inner.sort.index),
+            BlockKind::LeftTop => Some(&mut self.centre.inner.left_chunk.inner.top.index),
+            BlockKind::LeftBottom => Some(&mut self.centre.inner.left_chunk.inner.bottom.index),
+            BlockKind::Centre => Some(self.centre.inner.main.index()),
+            BlockKind::TopLeft | BlockKind::Bottom => None,
+        }
+    }
+
+    // that of the active block, or the hovered one when none is
+    pub fn focused_index(&mut self) -> Option<&mut Index> {
+        self.index(self.active.unwrap_or(self.hovered))
+    }
+
+    // the keymaps in effect, most specific first
+    pub fn keymaps(&self) -> Vec<Keymap> {
+        if let Some(popup) = &self.popup {
+            return vec![popup.keymap()];
+        }
+
+        match self.active {
+            // typing a query must not trigger the bindings of the keys typed
+            Some(BlockKind::TopLeft) => vec![Keymap::Block(BlockKind::TopLeft)],
+            Some(BlockKind::Centre) => vec![self.centre.inner.main.keymap(), Keymap::Block(BlockKind::Centre), Keymap::Global],
+            Some(blk) => vec![Keymap::Block(blk), Keymap::Global],
+            None => vec![Keymap::Hover, Keymap::Global],
+        }
+    }
+
+    // keys bound to nothing are typed into the command line, the help's filter or the search
+    pub fn input(state: &mut State, key: Key) {
+        let searching = state.chunks.is_active(BlockKind::TopLeft);
+        match &mut state.chunks.popup {
+            Some(Popup::CommandLine(line)) => line.input(key),
+            Some(Popup::Help(help)) => help.input(key),
+            Some(_) => {},
+            None if searching => StandardBlock::<Search>::input(state, key),
+            None => {},
+        }
+    }
+
+    // hands a command to the popup, or the active block, or the hovered one
+    pub async fn command_event(state: &mut State, cmd: Command) -> Result<()> {
+        match (&state.chunks.popup, state.chunks.active) {
+            (Some(_), _) => Popup::event(state, cmd).await,
+            (None, Some(_)) => Chunks::active_event(state, cmd).await,
+            (None, None) => Chunks::hovered_event(state, cmd).await,
+        }
+    }
+
+    pub async fn active_event(state: &mut State, cmd: Command) -> Result<()> {
         match state.chunks.active {
-            Some(BlockKind::LeftTop) => IndexedBlock::<Library>::active_event(state, key).await,
-            Some(BlockKind::LeftBottom) => IndexedBlock::<Playlists>::active_event(state, key).await,
+            Some(BlockKind::TopLeft) => StandardBlock::<Search>::active_event(state, cmd.clone()).await?,
+            Some(BlockKind::TopRight) => IndexedBlock::<Sort>::active_event(state, cmd.clone()).await?,
+            Some(BlockKind::LeftTop) => IndexedBlock::<Library>::active_event(state, cmd.clone()).await?,
+            Some(BlockKind::LeftBottom) => IndexedBlock::<Playlists>::active_event(state, cmd.clone()).await?,
+            Some(BlockKind::Centre) => MainBlock::active_event(state, cmd.clone()).await?,
             _ => {}
         }
 
-        match key {
-            Key::Esc => state.chunks.active=None,
+        match cmd {
+            Command::Cancel => state.chunks.active=None,
             _ => {}
         }
+
+        Ok(())
     }
 
-    pub async fn hovered_event(state: &mut State, key: Key) {
+    pub async fn hovered_event(state: &mut State, cmd: Command) -> Result<()> {
         match state.chunks.hovered {
             BlockKind::TopLeft => {
-                match key {
-                    Key::Down => state.chunks.set_hover(BlockKind::LeftTop),
-                    Key::Right => state.chunks.set_hover(BlockKind::TopRight),
+                match cmd {
+                    Command::HoverDown => state.chunks.set_hover(BlockKind::LeftTop),
+                    Command::HoverRight => state.chunks.set_hover(BlockKind::TopRight),
                     _ => {},
                 }
 
@@ -94,53 +424,53 @@ impl Chunks {
             }
 
             BlockKind::TopRight => {
-                match key {
-                    Key::Down => state.chunks.set_hover(BlockKind::Centre),
-                    Key::Left => state.chunks.set_hover(BlockKind::TopLeft),
+                match cmd {
+                    Command::HoverDown => state.chunks.set_hover(BlockKind::Centre),
+                    Command::HoverLeft => state.chunks.set_hover(BlockKind::TopLeft),
                     _ => {},
                 }
 
-                // IndexedBlock::<Sort>::hovered_event(state, key).await;
+                IndexedBlock::<Sort>::hovered_event(state, cmd.clone()).await?;
             }
 
             BlockKind::LeftTop => {
-                match key {
-                    Key::Up => state.chunks.set_hover(BlockKind::TopLeft),
-                    Key::Down => state.chunks.set_hover(BlockKind::LeftBottom),
-                    Key::Right => state.chunks.set_hover(BlockKind::Centre),
+                match cmd {
+                    Command::HoverUp => state.chunks.set_hover(BlockKind::TopLeft),
+                    Command::HoverDown => state.chunks.set_hover(BlockKind::LeftBottom),
+                    Command::HoverRight => state.chunks.set_hover(BlockKind::Centre),
                     _ => {},
                 }
 
-                IndexedBlock::<Library>::hovered_event(state, key).await;
+                IndexedBlock::<Library>::hovered_event(state, cmd.clone()).await?;
             }
 
             BlockKind::LeftBottom => {
-                match key {
-                    Key::Up => state.chunks.set_hover(BlockKind::TopLeft),
-                    Key::Down => state.chunks.set_hover(BlockKind::LeftBottom),
-                    Key::Right => state.chunks.set_hover(BlockKind::Centre),
+                match cmd {
+                    Command::HoverUp => state.chunks.set_hover(BlockKind::TopLeft),
+                    Command::HoverDown => state.chunks.set_hover(BlockKind::LeftBottom),
+                    Command::HoverRight => state.chunks.set_hover(BlockKind::Centre),
                     _ => {},
                 }
 
-                IndexedBlock::<Playlists>::hovered_event(state, key).await;
+                IndexedBlock::<Playlists>::hovered_event(state, cmd.clone()).await?;
             }
 
             BlockKind::Centre => {
-                match key {
-                    Key::Up => state.chunks.set_hover(BlockKind::TopLeft),
-                    Key::Left => {
+                match cmd {
+                    Command::HoverUp => state.chunks.set_hover(BlockKind::TopLeft),
+                    Command::HoverLeft => {
                         for previous in &state.chunks.hover_history {
                             if *previous==BlockKind::LeftTop || *previous==BlockKind::LeftBottom {
                                 state.chunks.set_hover(*previous);
-                                return;
+                                return Ok(());
                             }
                         }
 
                         state.chunks.set_hover(BlockKind::LeftTop)
                     },
 
-                    Key::Right => state.chunks.set_hover(BlockKind::TopRight),
-                    Key::Down => {
+                    Command::HoverRight => state.chunks.set_hover(BlockKind::TopRight),
+                    Command::HoverDown => {
                         state.chunks.set_active(BlockKind::Centre);
                     },
 
@@ -161,15 +491,202 @@ impl Chunks {
         // }
 
         // common behaviour
-        match key {
-            Key::Enter => state.chunks.set_active(state.chunks.hovered),
+        match cmd {
+            Command::Select => state.chunks.set_active(state.chunks.hovered),
             _ => {}
         }
+
+        Ok(())
     }   
+
+    // routes mpd's idle notifications to every block showing data from that
+    // subsystem, which is fetched again in the background
+    pub fn idle_event(state: &mut State, subsystem: Subsystem) {
+        let (client, events) = (state.client.clone(), state.events.clone());
+        match subsystem {
+            // the queue changing can move or remove the current song
+            Subsystem::Player | Subsystem::Mixer | Subsystem::Options | Subsystem::Update | Subsystem::Queue => {
+                state.chunks.playbar_fetch.spawn(|generation| async move {
+                    // sending only fails once the event loop is gone
+                    let _ = events.send(Event::PlaybarFetched(generation, Playbar::fetch(&client).await));
+                });
+            },
+
+            Subsystem::StoredPlaylist => {
+                state.chunks.playlists_fetch.spawn(|generation| async move {
+                    let playlists = client.command(commands::GetPlaylists).await.map_err(Into::into);
+                    let _ = events.send(Event::PlaylistsFetched(generation, playlists));
+                });
+            },
+
+            // a rebuild still running would finish with the old database
+            Subsystem::Database => {
+                if let Some(indexing) = state.indexing.take() {
+                    indexing.abort();
+                }
+
+                state.indexing = Some(LibraryIndex::rebuild(&state.client, &state.host, &state.events));
+                state.index_progress = None;
+                state.index_error = None;
+            },
+
+            _ => {}
+        }
+
+        // a view on its way may have been fetched before the change
+        let view = match &state.chunks.loading {
+            Some(loading) if loading.error.is_none() => loading.location.view.clone(),
+            _ => state.chunks.centre.inner.main.view(),
+        };
+
+        if view.depends_on(&subsystem) {
+            Chunks::reload(state);
+        }
+    }
+
+    // a failed fetch leaves the block as it was, with the reason shown
+    pub fn playbar_fetched(state: &mut State, generation: u64, playbar: Result<Playbar>) {
+        if !state.chunks.playbar_fetch.finish(generation) {
+            return;
+        }
+
+        match playbar {
+            Ok(playbar) => state.chunks.bottom.inner.playbar.inner = playbar,
+            Err(e) => state.error = Some(e.to_string()),
+        }
+    }
+
+    pub fn playlists_fetched(state: &mut State, generation: u64, playlists: Result<Vec<Playlist>>) {
+        if !state.chunks.playlists_fetch.finish(generation) {
+            return;
+        }
+
+        match playlists {
+            Ok(playlists) => state.chunks.centre.inner.left_chunk.inner.bottom.update(playlists),
+            Err(e) => state.error = Some(e.to_string()),
+        }
+    }
+
+    // where each shown block was drawn, worked out the same way rendering
+    // does, so the mouse can be matched to the block under it
+    pub fn regions(&self, layout: &LayoutConfig, size: Rect) -> Vec<(BlockKind, Rect)> {
+        let parent = crate::parent_layout(size);
+        let mut regions = Vec::new();
+
+        if self.top.show {
+            let top = Chunk::<Top>::split(parent[0]);
+            regions.push((BlockKind::TopLeft, top[0]));
+            regions.push((BlockKind::TopRight, top[1]));
+        }
+
+        let centre = self.centre.split(layout, parent[1]);
+        if self.centre.inner.left_chunk.show {
+            let left = Chunk::<Left>::split(layout, centre[0]);
+            regions.push((BlockKind::LeftTop, left[0]));
+            regions.push((BlockKind::LeftBottom, left[1]));
+        }
+
+        regions.push((BlockKind::Centre, centre[1]));
+
+        if self.bottom.show {
+            regions.push((BlockKind::Bottom, parent[2]));
+        }
+
+        regions
+    }
+
+    // the wheel moves the selection of the list under the mouse, a click
+    // focuses the block under it and selects the row clicked, and a double
+    // click plays or opens that row
+    pub async fn mouse_event(state: &mut State, mouse: Mouse) -> Result<()> {
+        let (column, row) = (mouse.column, mouse.row);
+        let (kind, area) = match state.chunks.regions(&state.layout, state.size).into_iter().find(|(_, area)| contains(*area, column, row)) {
+            Some(region) => region,
+            None => return Ok(()),
+        };
+
+        match mouse.kind {
+            MouseKind::ScrollUp | MouseKind::ScrollDown => {
+                if kind == BlockKind::Centre {
+                    state.chunks.centre.inner.main.focus_at(area, column, row);
+                }
+
+                let index = match state.chunks.index(kind) {
+                    Some(index) => index,
+                    None => return Ok(()),
+                };
+
+                for _ in 0..SCROLL_LINES {
+                    match mouse.kind {
+                        MouseKind::ScrollUp => index.dec(),
+                        _ => index.inc(),
+                    }
+                }
+            },
+
+            MouseKind::Click | MouseKind::DoubleClick => {
+                if kind == BlockKind::Bottom {
+                    return state.chunks.bottom.inner.playbar.seek_at(&state.client, area, column, row).await;
+                }
+
+                state.chunks.set_active(kind);
+
+                let left = &mut state.chunks.centre.inner.left_chunk.inner;
+                let hit = match kind {
+                    BlockKind::LeftTop => select_row(&mut left.top.index, area, row),
+                    BlockKind::LeftBottom => select_row(&mut left.bottom.index, area, row),
+                    BlockKind::Centre => state.chunks.centre.inner.main.select_at(area, column, row),
+                    _ => false,
+                };
+
+                if hit && mouse.kind == MouseKind::DoubleClick {
+                    match (kind, &state.chunks.centre.inner.main) {
+                        // songs already queued are played where they are
+                        (BlockKind::Centre, MainBlock::Queue(_)) => Chunks::active_event(state, Command::Select).await?,
+                        (BlockKind::Centre, _) => Command::PlayItem.execute(state, None).await?,
+                        _ => Chunks::active_event(state, Command::Select).await?,
+                    }
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    // an index that couldn't be built, eg. as the library is bigger than mpd
+    // will list at once, leaves searches and views going to mpd
+    pub fn library_indexed(state: &mut State, index: Result<LibraryIndex>) {
+        state.indexing = None;
+        state.index_progress = None;
+
+        match index {
+            Ok(index) => state.library = Arc::new(index),
+            Err(e) => {
+                state.index_error = Some(e.to_string());
+                return;
+            },
+        }
+
+        // shown results and lists came from mpd or the previous index
+        if !matches!(state.chunks.centre.inner.main, MainBlock::Queue(_)) || state.chunks.loading.is_some() {
+            Chunks::reload(state);
+        }
+    }
 }
 
-pub struct Top {
+// selects the row of a `selectable_list` under the mouse, telling whether there was one
+fn select_row(index: &mut Index, area: Rect, row: u16) -> bool {
+    let hit = list_row(area, index, row);
+    if let Some(i) = hit {
+        index.select(i);
+    }
 
+    hit.is_some()
+}
+
+pub struct Top {
+    pub search: StandardBlock<Search>,
+    pub sort: IndexedBlock<Sort>,
 }
 
 pub struct Left {
@@ -179,10 +696,11 @@ pub struct Left {
 
 pub struct Centre {
     pub left_chunk: Chunk<Left>,
+    pub main: MainBlock,
 }
 
 pub struct Bottom {
-
+    pub playbar: StandardBlock<Playbar>,
 }
 
 pub struct Chunk<T> {
@@ -191,52 +709,62 @@ pub struct Chunk<T> {
 }
 
 impl Chunk<Top> {
-    async fn new() -> Result<Self> {
-        Ok(Self {
-            show: true,
-            inner: Top {}
-        })
+    fn new(layout: &LayoutConfig) -> Self {
+        Self {
+            show: layout.show_top,
+            inner: Top {
+                search: StandardBlock::<Search>::new(),
+                sort: IndexedBlock::<Sort>::new(),
+            }
+        }
+    }
+
+    // search, then sort
+    fn split(area: Rect) -> Vec<Rect> {
+        Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(80), Constraint::Percentage(20)].as_ref())
+            .split(area)
     }
 }
 
 impl<B: Backend + Send> Render<B> for Chunk<Top> {
     fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
         if self.show {
-            let chunks = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Percentage(90), Constraint::Percentage(10)].as_ref())
-                .split(layout_chunk);
-
-            // state.chunks.search.render(f, state, chunks[0]);
-            // state.chunks.sort.render(f, state, chunks[1]);
+            let chunks = Self::split(layout_chunk);
+            self.inner.search.render(f, state, chunks[0]);
+            self.inner.sort.render(f, state, chunks[1]);
         }
     }
 }
 
 impl Chunk<Left> {
-    async fn new(client: &Client) -> Result<Self> {
-        Ok(Self {
-            show: true,
+    fn new(layout: &LayoutConfig) -> Self {
+        Self {
+            show: layout.show_left,
             inner: Left {
-                top: IndexedBlock::<Library>::new().await?,
-                bottom: IndexedBlock::<Playlists>::new(client).await?
+                top: IndexedBlock::<Library>::new(),
+                bottom: IndexedBlock::<Playlists>::new()
             }
-        })
+        }
+    }
+
+    // the library, then playlists
+    fn split(layout: &LayoutConfig, area: Rect) -> Vec<Rect> {
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Percentage(layout.library_height),
+                Constraint::Percentage(100 - layout.library_height.min(100))
+            ].as_ref())
+            .split(area)
     }
 }
 
 impl<B: Backend + Send> Render<B> for Chunk<Left> {
     fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
         if self.show {
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Percentage(30), 
-                    Constraint::Percentage(70)
-                ].as_ref())
-                .split(layout_chunk);
-
-
+            let chunks = Self::split(&state.layout, layout_chunk);
             self.inner.top.render(f, state, chunks[0]);
             self.inner.bottom.render(f, state, chunks[1]);
         }
@@ -244,37 +772,65 @@ impl<B: Backend + Send> Render<B> for Chunk<Left> {
 }
 
 impl Chunk<Centre> {
-    async fn new(client: &Client) -> Result<Self> {
-        Ok(Self {
+    fn new(layout: &LayoutConfig) -> Self {
+        Self {
             show: true,
             inner: Centre {
-                left_chunk: Chunk::<Left>::new(client).await?,
+                left_chunk: Chunk::<Left>::new(layout),
+                // empty until loaded in the background
+                main: MainBlock::Queue(IndexedBlock { index: Index::new(0), inner: Queue::default() }),
             }
-        })
+        }
     }
-}
 
+    // the left column, then the main view
+    fn split(&self, layout: &LayoutConfig, area: Rect) -> Vec<Rect> {
+        let left_width = if self.inner.left_chunk.show { layout.left_width.min(100) } else { 0 };
+        Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(left_width), Constraint::Percentage(100 - left_width)].as_ref())
+            .split(area)
+    }
+}
 
 impl<B: Backend + Send> Render<B> for Chunk<Centre> {
     fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect) {
         if self.show {
-            let chunks = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Percentage(20), Constraint::Percentage(80)].as_ref())
-                .split(layout_chunk);
+            let chunks = self.split(&state.layout, layout_chunk);
 
             self.inner.left_chunk.render(f, state, chunks[0]);
-            // state.chunks.main.render(f, state, chunks[1]);
+            self.inner.main.render(f, state, chunks[1]);
+
+            // a view still being fetched, or why it couldn't be, on the main view's top border
+            if let Some(loading) = &state.chunks.loading {
+                let label = loading.location.view.label();
+                let doing = match loading.step {
+                    Step::Refresh => "updating",
+                    _ => "loading",
+                };
+
+                let progress = match loading.progress {
+                    Some((done, total)) => format!(" {}/{}", count(done), count(total)),
+                    None => String::new(),
+                };
+
+                match &loading.error {
+                    None => border_note(f, chunks[1], &format!("{} {} {}{}", spinner(), doing, label, progress), Style::default().fg(state.theme.hint)),
+                    Some(e) => border_note(f, chunks[1], &format!("couldn't load {}: {}", label, e), Style::default().fg(state.theme.error_text)),
+                }
+            }
         }
     }
 }
 
 impl Chunk<Bottom> {
-    async fn new() -> Result<Self> {
-        Ok(Self {
+    fn new() -> Self {
+        Self {
             show: true,
-            inner: Bottom {}
-        })
+            inner: Bottom {
+                playbar: StandardBlock::<Playbar>::new(),
+            }
+        }
     }
 }
 
@@ -286,7 +842,12 @@ impl<B: Backend + Send> Render<B> for Chunk<Bottom> {
                 .constraints([Constraint::Percentage(100)].as_ref())
                 .split(layout_chunk);
 
-            // state.chunks.playbar.render(f, state, chunks[0]);
+            self.inner.playbar.render(f, state, chunks[0]);
+
+            // the count and keys of a binding still being typed, on the top border
+            if let Some(pending) = state.keys.pending() {
+                border_note(f, chunks[0], &pending, Style::default().fg(state.theme.playbar_text).add_modifier(Modifier::BOLD));
+            }
         }
     }
 }
\ No newline at end of file
@@ -1,21 +1,113 @@
 use async_trait::async_trait;
-use anyhow::Result;
-use mpd_client::{ Client, CommandError, commands, commands::responses::{ Song, PlayState }, filter::{ Operator, Filter }, tag::Tag };
+use anyhow::{ Context, Result };
+use mpd_client::{ Client, CommandError, state_changes::StateChanges, commands, commands::{ SongId, SongPosition, responses::{ Response, Song, PlayState } }, filter::Filter, tag::Tag, raw::{ Frame, RawCommand } };
+use tokio::io::{ AsyncRead, AsyncWrite };
+use tokio::net::{ TcpStream, UnixStream };
+use crate::config::{ Address, ConnectionConfig };
 
 use std::time::Duration;
 
+// the returned client has already sent the password, if any, so every command
+// issued through it runs with the permissions of that password
+pub async fn connect(config: &ConnectionConfig) -> Result<(Client, StateChanges)> {
+    let address = config.address();
+    let password = config.password.as_deref();
+
+    let connection = match &address {
+        Address::Tcp(host, port) => {
+            let stream = TcpStream::connect((host.as_str(), *port)).await
+                .with_context(|| format!("could not connect to mpd at {}", address))?;
+            handshake(stream, password).await
+        },
+
+        Address::Socket(path) => {
+            let stream = UnixStream::connect(path).await
+                .with_context(|| format!("could not connect to mpd at {}", address))?;
+            handshake(stream, password).await
+        },
+    };
+
+    connection.with_context(|| format!("could not authenticate with mpd at {}", address))
+}
+
+async fn handshake<C>(stream: C, password: Option<&str>) -> Result<(Client, StateChanges)>
+where
+    C: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    Ok(match password {
+        Some(password) => Client::connect_with_password(stream, password).await?,
+        None => Client::connect(stream).await?,
+    })
+}
+
 #[async_trait]
 pub trait StrofaClient {
     async fn toggle_playback(&self) -> Result<(), CommandError>;
     async fn set_volume(&self, o: i8) -> Result<(), CommandError>;
+    async fn set_volume_to(&self, volume: u8) -> Result<(), CommandError>;
+    async fn set_crossfade(&self, duration: Duration) -> Result<(), CommandError>;
+    async fn outputs(&self) -> Result<Vec<Output>, CommandError>;
+    async fn toggle_output(&self, name: &str) -> Result<bool, CommandError>;
     async fn next_track(&self) -> Result<(), CommandError>;
     async fn previous_track(&self) -> Result<(), CommandError>;
+    async fn skip_tracks(&self, n: isize) -> Result<(), CommandError>;
     async fn seek_forwards(&self, o: u64) -> Result<(), CommandError>;
     async fn seek_backwards(&self, o: u64) -> Result<(), CommandError>;
+    async fn seek_to(&self, position: Duration) -> Result<(), CommandError>;
     async fn toggle_shuffle(&self) -> Result<(), CommandError>;
     async fn toggle_repeat(&self) -> Result<(), CommandError>;
-    async fn search(&self, query: &str) -> Result<Vec<Song>, CommandError>;
+    async fn search(&self, filter: Filter) -> Result<Vec<Song>, CommandError>;
     async fn clear_queue(&self) -> Result<(), CommandError>;
+    async fn delete_from_queue(&self, id: SongId) -> Result<(), CommandError>;
+    async fn move_in_queue(&self, id: SongId, to: usize) -> Result<(), CommandError>;
+    async fn crop_queue(&self) -> Result<(), CommandError>;
+    async fn save_queue(&self, playlist: &str) -> Result<(), CommandError>;
+    async fn add_to_playlist(&self, playlist: &str, uri: &str) -> Result<(), CommandError>;
+    async fn add_to_queue(&self, uris: &[String]) -> Result<Option<SongId>, CommandError>;
+    async fn play_uris(&self, uris: &[String]) -> Result<(), CommandError>;
+    async fn list_library(&self, progress: &Progress<'_>) -> Result<Vec<Song>, CommandError>;
+    async fn modified_since(&self, since: u64) -> Result<Vec<Song>, CommandError>;
+}
+
+// the songs of a response to a command mpd_client has no typed version of
+fn songs(frame: Frame) -> Result<Vec<Song>, CommandError> {
+    Ok(Vec::<Song>::from_frame(frame)?)
+}
+
+// an audio output as mpd lists it, which mpd_client has no command for
+pub struct Output {
+    pub id: u32,
+    pub name: String,
+}
+
+// told how many songs are done out of how many there are
+pub type Progress<'a> = dyn Fn(usize, usize) + Send + Sync + 'a;
+
+pub fn first_tag<'a>(song: &'a Song, tag: &Tag) -> Option<&'a str> {
+    song.tags.get(tag)?.first().map(String::as_str)
+}
+
+// songs without an album artist are filed under their artist, like most players do
+pub fn album_artist(song: &Song) -> &str {
+    song.album_artists().first()
+        .or_else(|| song.artists().first())
+        .map(String::as_str)
+        .unwrap_or_default()
+}
+
+// when the music was first released, rather than when this edition was
+pub fn release_date(song: &Song) -> Option<&str> {
+    first_tag(song, &Tag::OriginalDate).or_else(|| first_tag(song, &Tag::Date))
+}
+
+// mpd has no language tag of its own, this is only set when the server passes one through
+pub fn language(song: &Song) -> Option<&str> {
+    first_tag(song, &Tag::Other("Language".into()))
+}
+
+pub fn year(song: &Song) -> Option<&str> {
+    let date = first_tag(song, &Tag::Date)?;
+    date.get(..4).or(Some(date))
 }
 
 #[async_trait]
@@ -31,17 +123,48 @@ impl StrofaClient for Client {
 
     async fn set_volume(&self, o: i8) -> Result<(), CommandError> {
         let current_volume = self.command(commands::Status).await?.volume;
-        let new_volume = (current_volume as i8)+o;
-        
-        let vol = if new_volume < 0 {
-            0
-        } else if new_volume > 100 {
-            100
-        } else {
-            new_volume
-        };
+        // wide enough for any volume and change, and within a u8 once clamped
+        let volume = (i16::from(current_volume) + i16::from(o)).clamp(0, 100) as u8;
+        self.command(commands::SetVolume(volume)).await
+    }
+
+    async fn set_volume_to(&self, volume: u8) -> Result<(), CommandError> {
+        self.command(commands::SetVolume(volume)).await
+    }
+
+    async fn set_crossfade(&self, duration: Duration) -> Result<(), CommandError> {
+        self.command(commands::Crossfade(duration)).await
+    }
 
-        self.command(commands::SetVolume(vol.try_into().unwrap())).await
+    // every output starts at its id, the fields after it up to the next id being its own
+    async fn outputs(&self) -> Result<Vec<Output>, CommandError> {
+        let mut outputs = Vec::new();
+        for (key, value) in self.raw_command(RawCommand::new("outputs")).await? {
+            match (&*key, outputs.last_mut()) {
+                ("outputid", _) => match value.parse() {
+                    Ok(id) => outputs.push(Output { id, name: String::new() }),
+                    Err(_) => continue,
+                },
+
+                ("outputname", Some(output)) => output.name = value,
+                _ => continue,
+            }
+        }
+
+        Ok(outputs)
+    }
+
+    // telling whether there was an output by that name
+    async fn toggle_output(&self, name: &str) -> Result<bool, CommandError> {
+        let outputs = self.outputs().await?;
+        match outputs.iter().find(|output| output.name == name) {
+            Some(output) => {
+                self.raw_command(RawCommand::new("toggleoutput").argument(output.id.to_string())).await?;
+                Ok(true)
+            },
+
+            None => Ok(false),
+        }
     }
 
     async fn next_track(&self) -> Result<(), CommandError> {
@@ -52,6 +175,30 @@ impl StrofaClient for Client {
         self.command(commands::Previous).await
     }
 
+    // `n` songs ahead, or back when negative, in one go rather than asking
+    // mpd for the next song that many times; shuffled, only mpd knows what
+    // comes next, so that is done a song at a time after all
+    async fn skip_tracks(&self, n: isize) -> Result<(), CommandError> {
+        let status = self.command(commands::Status).await?;
+        match status.current_song {
+            Some((SongPosition(current), _)) if !status.random => {
+                let target = current.saturating_add_signed(n).min(status.playlist_length.saturating_sub(1));
+                self.raw_command(RawCommand::new("play").argument(target.to_string())).await?;
+            },
+
+            _ => {
+                for _ in 0..n.unsigned_abs().min(status.playlist_length) {
+                    match n < 0 {
+                        true => self.previous_track().await?,
+                        false => self.next_track().await?,
+                    }
+                }
+            },
+        }
+
+        Ok(())
+    }
+
     async fn seek_forwards(&self, o: u64) -> Result<(), CommandError> {
         self.command(commands::Seek(commands::SeekMode::Forward(Duration::from_secs(o)))).await
     }
@@ -60,6 +207,10 @@ impl StrofaClient for Client {
         self.command(commands::Seek(commands::SeekMode::Backward(Duration::from_secs(o)))).await
     }
 
+    async fn seek_to(&self, position: Duration) -> Result<(), CommandError> {
+        self.command(commands::Seek(commands::SeekMode::Absolute(position))).await
+    }
+
     async fn toggle_shuffle(&self) -> Result<(), CommandError> {
         let current_shuffle = self.command(commands::Status).await?.random;
         self.command(commands::SetRandom(!current_shuffle)).await
@@ -78,12 +229,90 @@ impl StrofaClient for Client {
     //     }
     // }
 
-    async fn search(&self, query: &str) -> Result<Vec<Song>, CommandError> {
-        let filter = Filter::new(Tag::Name, Operator::Contain, query.to_string());
-        self.command(commands::Find::new(filter)).await
+    // unlike `find`, ignores case; mpd_client only has `find`
+    async fn search(&self, filter: Filter) -> Result<Vec<Song>, CommandError> {
+        songs(self.raw_command(RawCommand::new("search").argument(filter)).await?)
     }
 
     async fn clear_queue(&self) -> Result<(), CommandError> {
         self.command(commands::ClearQueue).await
     }
+
+    async fn delete_from_queue(&self, id: SongId) -> Result<(), CommandError> {
+        self.command(commands::Delete::id(id)).await
+    }
+
+    async fn move_in_queue(&self, id: SongId, to: usize) -> Result<(), CommandError> {
+        self.command(commands::Move::id(id).to_position(SongPosition(to))).await
+    }
+
+    // removes everything but the current song, leaving the queue untouched when nothing is playing
+    async fn crop_queue(&self) -> Result<(), CommandError> {
+        let status = self.command(commands::Status).await?;
+        if let Some((SongPosition(current), _)) = status.current_song {
+            // delete after the current song first so its position stays valid
+            if current + 1 < status.playlist_length {
+                self.raw_command(RawCommand::new("delete").argument(format!("{}:", current + 1))).await?;
+            }
+
+            if current > 0 {
+                self.raw_command(RawCommand::new("delete").argument(format!("0:{}", current))).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn save_queue(&self, playlist: &str) -> Result<(), CommandError> {
+        self.command(commands::SaveQueueAsPlaylist(playlist.to_string())).await
+    }
+
+    async fn add_to_playlist(&self, playlist: &str, uri: &str) -> Result<(), CommandError> {
+        self.raw_command(RawCommand::new("playlistadd").argument(playlist.to_string()).argument(uri.to_string())).await?;
+        Ok(())
+    }
+
+    // returns the id of the first song added, which is where playback of the batch starts
+    async fn add_to_queue(&self, uris: &[String]) -> Result<Option<SongId>, CommandError> {
+        let mut first = None;
+        for uri in uris {
+            let id = self.command(commands::Add::uri(uri.clone())).await?;
+            first.get_or_insert(id);
+        }
+
+        Ok(first)
+    }
+
+    async fn play_uris(&self, uris: &[String]) -> Result<(), CommandError> {
+        match self.add_to_queue(uris).await? {
+            Some(id) => self.command(commands::Play::song(id)).await,
+            None => Ok(()),
+        }
+    }
+
+    // a top level directory at a time, as mpd gives up on listing a big
+    // library at once, reporting progress after each
+    async fn list_library(&self, progress: &Progress<'_>) -> Result<Vec<Song>, CommandError> {
+        let total = self.command(commands::Stats).await?.songs as usize;
+        let mut songs = Vec::with_capacity(total);
+
+        for (key, value) in self.raw_command(RawCommand::new("lsinfo")).await? {
+            match &*key {
+                "directory" => songs.extend(self.command(commands::ListAllIn::directory(value)).await?),
+                // songs kept right at the root
+                "file" => songs.extend(self.command(commands::Find::new(Filter::tag(Tag::Other("file".into()), value))).await?),
+                _ => continue,
+            }
+
+            progress(songs.len(), total);
+        }
+
+        Ok(songs)
+    }
+
+    // `since` is in seconds since the epoch, like the database update in mpd's stats
+    async fn modified_since(&self, since: u64) -> Result<Vec<Song>, CommandError> {
+        let filter = format!("(modified-since '{}')", since);
+        songs(self.raw_command(RawCommand::new("find").argument(filter)).await?)
+    }
 }
\ No newline at end of file
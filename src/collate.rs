@@ -0,0 +1,186 @@
+use crate::client::{ album_artist, first_tag };
+use mpd_client::{ commands::responses::Song, tag::Tag };
+use std::sync::OnceLock;
+
+pub const DEFAULT_ARTICLES: [&str; 14] = ["The", "A", "An", "Les", "Le", "La", "L'", "Die", "Der", "Das", "El", "Los", "Las", "Il"];
+
+// set once from the config at startup, every listing sorts the same way after that
+static ARTICLES: OnceLock<Vec<String>> = OnceLock::new();
+
+pub fn init(articles: &[String]) {
+    let _ = ARTICLES.set(articles.iter().map(|article| fold(article)).collect());
+}
+
+// orders names the way people look them up: "The Beatles" under B, "Track 2"
+// before "Track 10" and "Émilie" next to "Emilie"
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Collated {
+    parts: Vec<Part>,
+    // names only differing in case or accents still need a fixed order
+    original: String,
+}
+
+// numbers come before text, like most file managers list them
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Part {
+    // digit count and digits without leading zeros, which compare numerically that way
+    Number(usize, String),
+    Text(String),
+}
+
+pub fn key(name: &str) -> Collated {
+    let folded = fold(name);
+    let mut rest = strip_article(&folded);
+
+    // "...And You Will Know Us" under A
+    let trimmed = rest.trim_start_matches(|c: char| !c.is_alphanumeric());
+    if !trimmed.is_empty() {
+        rest = trimmed;
+    }
+
+    let mut parts = Vec::new();
+    let mut chars = rest.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        let is_digit = c.is_ascii_digit();
+        let mut run = String::new();
+        while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() == is_digit) {
+            run.push(c);
+            chars.next();
+        }
+
+        parts.push(match is_digit {
+            true => {
+                let digits = run.trim_start_matches('0').to_string();
+                Part::Number(digits.len(), digits)
+            },
+
+            false => Part::Text(run),
+        });
+    }
+
+    Collated { parts, original: name.to_string() }
+}
+
+// the sort tags are what taggers fill in for exactly this, eg. "Bowie, David"
+pub fn artist_key(song: &Song) -> Collated {
+    let artist = first_tag(song, &Tag::ArtistSort)
+        .or_else(|| song.artists().first().map(String::as_str))
+        .unwrap_or_default();
+
+    key(artist)
+}
+
+pub fn album_artist_key(song: &Song) -> Collated {
+    match (first_tag(song, &Tag::AlbumArtistSort), song.album_artists().first()) {
+        (Some(sort), _) => key(sort),
+        (None, Some(_)) => key(album_artist(song)),
+        // filed under their artist, see `album_artist`
+        (None, None) => artist_key(song),
+    }
+}
+
+// each album artist of a song with what it sorts by, taggers listing the
+// sort names in the same order as the names
+pub fn album_artist_keys(song: &Song) -> Vec<(&str, Collated)> {
+    let sorts = song.tags.get(&Tag::AlbumArtistSort).map(Vec::as_slice).unwrap_or_default();
+    song.album_artists()
+        .iter()
+        .enumerate()
+        .map(|(i, artist)| (artist.as_str(), key(sorts.get(i).unwrap_or(artist))))
+        .collect()
+}
+
+pub fn album_key(song: &Song) -> Collated {
+    key(first_tag(song, &Tag::AlbumSort).or_else(|| song.album()).unwrap_or_default())
+}
+
+fn strip_article(name: &str) -> &str {
+    let articles = ARTICLES.get_or_init(|| DEFAULT_ARTICLES.iter().map(|article| fold(article)).collect());
+
+    for article in articles {
+        if let Some(rest) = name.strip_prefix(article.as_str()) {
+            // elided articles like "l'" run straight into the word, the others need a space
+            let rest = match article.ends_with('\'') {
+                true => rest,
+                false => match rest.strip_prefix(' ') {
+                    Some(rest) => rest,
+                    None => continue,
+                },
+            };
+
+            // a name that is only an article keeps it, "The The" goes under T
+            if !rest.trim().is_empty() {
+                return rest;
+            }
+        }
+    }
+
+    name
+}
+
+// case folding, along with dropping the accents of latin letters; a full
+// locale's alphabet, with eg. swedish å after z, is out of reach without icu
+fn fold(s: &str) -> String {
+    let mut folded = String::with_capacity(s.len());
+
+    for c in s.chars().flat_map(char::to_lowercase) {
+        match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => folded.push('a'),
+            'ç' | 'ć' | 'č' => folded.push('c'),
+            'ď' | 'đ' | 'ð' => folded.push('d'),
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => folded.push('e'),
+            'ì' | 'í' | 'î' | 'ï' | 'ī' | 'į' | 'ı' => folded.push('i'),
+            'ľ' | 'ĺ' | 'ł' => folded.push('l'),
+            'ñ' | 'ń' | 'ň' => folded.push('n'),
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => folded.push('o'),
+            'ŕ' | 'ř' => folded.push('r'),
+            'ś' | 'š' | 'ş' => folded.push('s'),
+            'ť' | 'ţ' => folded.push('t'),
+            'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' | 'ų' => folded.push('u'),
+            'ý' | 'ÿ' => folded.push('y'),
+            'ź' | 'ż' | 'ž' => folded.push('z'),
+            'æ' => folded.push_str("ae"),
+            'œ' => folded.push_str("oe"),
+            'ß' => folded.push_str("ss"),
+            'þ' => folded.push_str("th"),
+            '’' => folded.push('\''),
+            c => folded.push(c),
+        }
+    }
+
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(names: &[&str]) -> Vec<String> {
+        let mut names: Vec<(Collated, &str)> = names.iter().map(|name| (key(name), *name)).collect();
+        names.sort();
+        names.into_iter().map(|(_, name)| name.to_string()).collect()
+    }
+
+    #[test]
+    fn articles() {
+        assert_eq!(sorted(&["The Beatles", "Blur", "Abba", "L'Impératrice", "Los Lobos"]), ["Abba", "The Beatles", "Blur", "L'Impératrice", "Los Lobos"]);
+        // only whole words, and never the whole name
+        assert_eq!(key("Theatre").parts, key("theatre").parts);
+        assert_eq!(key("The Theatre").parts, key("Theatre").parts);
+        assert_eq!(key("The The").parts, [Part::Text(String::from("the"))]);
+        assert_eq!(key("...And You Will Know Us").parts, key("And You Will Know Us").parts);
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(sorted(&["Track 10", "Track 2", "Track 1", "Track 02b"]), ["Track 1", "Track 2", "Track 02b", "Track 10"]);
+        assert_eq!(sorted(&["Zoo", "99 Luftballons", "5ive"]), ["5ive", "99 Luftballons", "Zoo"]);
+    }
+
+    #[test]
+    fn folding() {
+        assert_eq!(key("Émilie").parts, key("emilie").parts);
+        assert_eq!(key("Straße").parts, key("STRASSE").parts);
+        assert_eq!(sorted(&["Emilie", "Zebra", "Émilie", "emilie"]), ["Emilie", "emilie", "Émilie", "Zebra"]);
+    }
+}
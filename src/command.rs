@@ -0,0 +1,488 @@
+use crate::block::{ IndexedBlock, StandardBlock, MainBlock, Search, Popup, Confirm, Help, CommandLine, AlbumKind, TrackKind };
+use crate::chunk::{ Chunks, BlockKind, Step };
+use crate::history::{ View, Location };
+use crate::client::StrofaClient;
+use crate::state::State;
+
+use anyhow::{ Result, bail };
+use std::time::Duration;
+use serde::{ Deserialize, Deserializer, de };
+
+// every action a key can be bound to; the textual form is what appears in
+// the config file and the command line, eg. `set_volume -5` or `focus left_bottom`
+#[derive(Clone, PartialEq, Debug)]
+#[allow(clippy::enum_variant_names)] // `CommandLine` is the command line, not a kind of command
+pub enum Command {
+    // ui
+    Back,
+    Forward,
+    ToQueue,
+    ToPlaylists,
+    ToggleTop,
+    // searches straight away when given a query
+    Search(Option<String>),
+    Help,
+    CommandLine,
+    Focus(BlockKind),
+    AddItemToQueue,
+    PlayItem,
+    DeleteItem,
+    MoveDown,
+    MoveUp,
+    PageDown,
+    PageUp,
+    JumpToStart,
+    JumpToEnd,
+    JumpToAlbum,
+    JumpToArtist,
+    HoverUp,
+    HoverDown,
+    HoverLeft,
+    HoverRight,
+
+    // up to the active block or popup
+    Select,
+    Cancel,
+    MoveLeft,
+    MoveRight,
+    NextPane,
+    DeleteBackward,
+    DeleteWord,
+    ClearLine,
+    DropSortKey,
+    MoveItemUp,
+    MoveItemDown,
+    PlayNext,
+    JumpToPlaying,
+    // asks which playlist when not given one
+    AddToPlaylist(Option<String>),
+    AskClearQueue,
+    Complete,
+
+    // mpd
+    ClearQueue,
+    CropQueue,
+    SaveQueue(String),
+    TogglePlayback,
+    SetVolume(i8),
+    Volume(u8),
+    Crossfade(u64),
+    ToggleOutput(String),
+    Seek(i64),
+    NextTrack,
+    PreviousTrack,
+    Shuffle,
+    Repeat,
+}
+
+impl Command {
+    // `count` is the number typed before the key, repeating or scaling the
+    // command, or picking the row to jump to
+    pub async fn execute(self, state: &mut State, count: Option<usize>) -> Result<()> {
+        let times = count.unwrap_or(1);
+
+        // no wildcard arm, adding a command without handling it will not compile
+        match self {
+            Command::ToQueue => Chunks::load(state, Location::new(View::Queue), Step::Visit),
+            Command::Back => Chunks::back(state),
+            Command::Forward => Chunks::forward(state),
+            Command::ToPlaylists => state.chunks.set_active(BlockKind::LeftBottom),
+            Command::ToggleTop => state.chunks.top.show = !state.chunks.top.show,
+            Command::Search(None) => state.chunks.set_active(BlockKind::TopLeft),
+            Command::Search(Some(query)) => StandardBlock::<Search>::submit(state, query),
+
+            // lists the bindings of wherever it was asked for
+            Command::Help => {
+                let help = IndexedBlock::<Help>::new(&state.keys, &state.chunks.keymaps());
+                state.chunks.popup = Some(Popup::Help(help));
+            },
+
+            Command::CommandLine => {
+                let line = CommandLine::new(&state.client).await?;
+                state.chunks.popup = Some(Popup::CommandLine(line));
+            },
+
+            Command::Focus(blk) => state.chunks.set_active(blk),
+
+            Command::AddItemToQueue => {
+                let uris = state.chunks.centre.inner.main.selected_uris(&state.client).await?;
+                state.client.add_to_queue(&uris).await?;
+            },
+
+            Command::PlayItem => {
+                let uris = state.chunks.centre.inner.main.selected_uris(&state.client).await?;
+                state.client.play_uris(&uris).await?;
+            },
+
+            // removes as many songs as counted, starting at the selected one
+            Command::DeleteItem => {
+                if let MainBlock::Queue(queue) = &state.chunks.centre.inner.main {
+                    let ids = queue.inner.songs.iter().skip(queue.index.inner).take(times).map(|song| song.id).collect::<Vec<_>>();
+                    for id in ids {
+                        state.client.delete_from_queue(id).await?;
+                    }
+                }
+            },
+
+            // text being typed and popups have their own idea of these
+            Command::MoveDown | Command::MoveUp | Command::PageDown | Command::PageUp | Command::JumpToStart | Command::JumpToEnd
+                if state.chunks.popup.is_some() || state.chunks.is_active(BlockKind::TopLeft) => Chunks::command_event(state, self).await?,
+
+            Command::MoveDown | Command::MoveUp | Command::PageDown | Command::PageUp | Command::JumpToStart | Command::JumpToEnd => {
+                let wrap = state.layout.wrap;
+                if let Some(index) = state.chunks.focused_index() {
+                    index.navigate(&self, count, wrap);
+                }
+            },
+
+            // songs without an album have nowhere to go
+            Command::JumpToAlbum => {
+                if let Some((artist, album)) = state.chunks.centre.inner.main.selected_album().filter(|(_, album)| !album.is_empty()) {
+                    Chunks::load(state, Location::new(View::Tracks(TrackKind::Album(artist, album))), Step::Visit);
+                }
+            },
+
+            Command::JumpToArtist => {
+                if let Some((artist, _)) = state.chunks.centre.inner.main.selected_album().filter(|(artist, _)| !artist.is_empty()) {
+                    Chunks::load(state, Location::new(View::Albums(AlbumKind::Artist(artist))), Step::Visit);
+                }
+            },
+
+            Command::HoverUp | Command::HoverDown | Command::HoverLeft | Command::HoverRight => Chunks::hovered_event(state, self).await?,
+
+            Command::Select | Command::Cancel | Command::MoveLeft | Command::MoveRight | Command::NextPane
+                | Command::DeleteBackward | Command::DeleteWord | Command::ClearLine | Command::DropSortKey
+                | Command::MoveItemUp | Command::MoveItemDown | Command::PlayNext | Command::JumpToPlaying
+                | Command::AddToPlaylist(None) | Command::Complete => Chunks::command_event(state, self).await?,
+
+            Command::AddToPlaylist(Some(playlist)) => {
+                let uris = state.chunks.centre.inner.main.selected_uris(&state.client).await?;
+                for uri in uris {
+                    state.client.add_to_playlist(&playlist, &uri).await?;
+                }
+            },
+
+            Command::AskClearQueue => {
+                let confirm = Confirm::new("Clear the queue?", Command::ClearQueue);
+                state.chunks.popup = Some(Popup::Confirm(confirm));
+            },
+
+            Command::ClearQueue => state.client.clear_queue().await?,
+            Command::CropQueue => state.client.crop_queue().await?,
+            Command::SaveQueue(name) => state.client.save_queue(&name).await?,
+            Command::TogglePlayback => state.client.toggle_playback().await?,
+            Command::SetVolume(o) => state.client.set_volume(i64::from(o).saturating_mul(times as i64).clamp(-100, 100) as i8).await?,
+            Command::Volume(volume) => state.client.set_volume_to(volume.min(100)).await?,
+            Command::Crossfade(seconds) => state.client.set_crossfade(Duration::from_secs(seconds)).await?,
+            Command::ToggleOutput(name) => {
+                if !state.client.toggle_output(&name).await? {
+                    bail!("no output named `{}`", name);
+                }
+            },
+
+            Command::Seek(o) if o < 0 => state.client.seek_backwards(o.unsigned_abs().saturating_mul(times as u64)).await?,
+            Command::Seek(o) => state.client.seek_forwards((o as u64).saturating_mul(times as u64)).await?,
+
+            Command::NextTrack if times == 1 => state.client.next_track().await?,
+            Command::NextTrack => state.client.skip_tracks(times as isize).await?,
+            Command::PreviousTrack if times == 1 => state.client.previous_track().await?,
+            Command::PreviousTrack => state.client.skip_tracks(-(times as isize)).await?,
+
+            Command::Shuffle => state.client.toggle_shuffle().await?,
+            Command::Repeat => state.client.toggle_repeat().await?,
+        }
+
+        Ok(())
+    }
+}
+
+impl Command {
+    // what the command does, as listed in the help
+    pub fn describe(&self) -> String {
+        let description = match self {
+            Command::Back => "go back to the previous view",
+            Command::Forward => "go forward to the next view",
+            Command::ToQueue => "show the queue",
+            Command::ToPlaylists => "focus the playlists",
+            Command::ToggleTop => "show or hide the search and sort",
+            Command::Search(None) => "focus the search",
+            Command::Search(Some(query)) => return format!("search for {}", query),
+            Command::Help => "list the key bindings",
+            Command::CommandLine => "enter a command",
+            Command::Focus(blk) => return format!("focus the {} block", blk),
+            Command::AddItemToQueue => "add the selection to the queue",
+            Command::PlayItem => "play the selection",
+            Command::DeleteItem => "remove the selected song from the queue",
+            Command::MoveDown => "move down",
+            Command::MoveUp => "move up",
+            Command::PageDown => "move down a page",
+            Command::PageUp => "move up a page",
+            Command::JumpToStart => "go to the start",
+            Command::JumpToEnd => "go to the end",
+            Command::JumpToAlbum => "go to the selected song's album",
+            Command::JumpToArtist => "go to the selected song's artist",
+            Command::HoverUp => "hover the block above",
+            Command::HoverDown => "hover the block below",
+            Command::HoverLeft => "hover the block to the left",
+            Command::HoverRight => "hover the block to the right",
+            Command::Select => "select",
+            Command::Cancel => "cancel or leave the block",
+            Command::MoveLeft => "move left",
+            Command::MoveRight => "move right",
+            Command::NextPane => "go to the next pane",
+            Command::DeleteBackward => "delete the character before the cursor",
+            Command::DeleteWord => "delete the word before the cursor",
+            Command::ClearLine => "delete everything before the cursor",
+            Command::DropSortKey => "stop sorting by the selected key",
+            Command::MoveItemUp => "move the selected song up",
+            Command::MoveItemDown => "move the selected song down",
+            Command::PlayNext => "play the selected song next",
+            Command::JumpToPlaying => "go to the song playing",
+            Command::AddToPlaylist(None) => "add the selected song to a playlist",
+            Command::AddToPlaylist(Some(playlist)) => return format!("add the selection to {}", playlist),
+            Command::AskClearQueue => "clear the queue, once confirmed",
+            Command::Complete => "complete the word typed",
+            Command::ClearQueue => "clear the queue",
+            Command::CropQueue => "remove every song but the one playing",
+            Command::SaveQueue(name) => return format!("save the queue as {}", name),
+            Command::TogglePlayback => "play or pause",
+            Command::SetVolume(o) if *o < 0 => return format!("lower the volume by {}%", o.unsigned_abs()),
+            Command::SetVolume(o) => return format!("raise the volume by {}%", o),
+            Command::Volume(volume) => return format!("set the volume to {}%", volume),
+            Command::Crossfade(0) => "stop crossfading",
+            Command::Crossfade(seconds) => return format!("crossfade songs over {}s", seconds),
+            Command::ToggleOutput(name) => return format!("enable or disable the output {}", name),
+            Command::Seek(o) if *o < 0 => return format!("seek back {}s", o.unsigned_abs()),
+            Command::Seek(o) => return format!("seek forward {}s", o),
+            Command::NextTrack => "play the next song",
+            Command::PreviousTrack => "play the previous song",
+            Command::Shuffle => "toggle shuffle",
+            Command::Repeat => "toggle repeat",
+        };
+
+        description.to_string()
+    }
+}
+
+impl std::fmt::Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Command::Back => write!(f, "back"),
+            Command::Forward => write!(f, "forward"),
+            Command::ToQueue => write!(f, "to_queue"),
+            Command::ToPlaylists => write!(f, "to_playlists"),
+            Command::ToggleTop => write!(f, "toggle_top"),
+            Command::Search(None) => write!(f, "search"),
+            Command::Search(Some(query)) => write!(f, "search {}", query),
+            Command::Help => write!(f, "help"),
+            Command::CommandLine => write!(f, "command_line"),
+            Command::Focus(blk) => write!(f, "focus {}", blk),
+            Command::AddItemToQueue => write!(f, "add_item_to_queue"),
+            Command::PlayItem => write!(f, "play_item"),
+            Command::DeleteItem => write!(f, "delete_item"),
+            Command::MoveDown => write!(f, "move_down"),
+            Command::MoveUp => write!(f, "move_up"),
+            Command::JumpToStart => write!(f, "jump_to_start"),
+            Command::JumpToEnd => write!(f, "jump_to_end"),
+            Command::PageDown => write!(f, "page_down"),
+            Command::PageUp => write!(f, "page_up"),
+            Command::JumpToAlbum => write!(f, "jump_to_album"),
+            Command::JumpToArtist => write!(f, "jump_to_artist"),
+            Command::HoverUp => write!(f, "hover_up"),
+            Command::HoverDown => write!(f, "hover_down"),
+            Command::HoverLeft => write!(f, "hover_left"),
+            Command::HoverRight => write!(f, "hover_right"),
+            Command::Select => write!(f, "select"),
+            Command::Cancel => write!(f, "cancel"),
+            Command::MoveLeft => write!(f, "move_left"),
+            Command::MoveRight => write!(f, "move_right"),
+            Command::NextPane => write!(f, "next_pane"),
+            Command::DeleteBackward => write!(f, "delete_backward"),
+            Command::DeleteWord => write!(f, "delete_word"),
+            Command::ClearLine => write!(f, "clear_line"),
+            Command::DropSortKey => write!(f, "drop_sort_key"),
+            Command::MoveItemUp => write!(f, "move_item_up"),
+            Command::MoveItemDown => write!(f, "move_item_down"),
+            Command::PlayNext => write!(f, "play_next"),
+            Command::JumpToPlaying => write!(f, "jump_to_playing"),
+            Command::AddToPlaylist(None) => write!(f, "add_to_playlist"),
+            Command::AddToPlaylist(Some(playlist)) => write!(f, "add_to_playlist {}", playlist),
+            Command::AskClearQueue => write!(f, "ask_clear_queue"),
+            Command::Complete => write!(f, "complete"),
+            Command::ClearQueue => write!(f, "clear_queue"),
+            Command::CropQueue => write!(f, "crop_queue"),
+            Command::SaveQueue(name) => write!(f, "save_queue {}", name),
+            Command::TogglePlayback => write!(f, "toggle_playback"),
+            Command::SetVolume(o) => write!(f, "set_volume {:+}", o),
+            Command::Volume(volume) => write!(f, "volume {}", volume),
+            Command::Crossfade(seconds) => write!(f, "crossfade {}", seconds),
+            Command::ToggleOutput(name) => write!(f, "toggle_output {}", name),
+            Command::Seek(o) => write!(f, "seek {:+}", o),
+            Command::NextTrack => write!(f, "next_track"),
+            Command::PreviousTrack => write!(f, "previous_track"),
+            Command::Shuffle => write!(f, "shuffle"),
+            Command::Repeat => write!(f, "repeat"),
+        }
+    }
+}
+
+impl std::str::FromStr for Command {
+    type Err = String;
+
+    // the argument is the rest of the line, so names can have spaces, and
+    // names can be written with dashes too, eg. `add-to-playlist Road Trip`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match s.trim().split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, Some(arg.trim())),
+            None => (s.trim(), None),
+        };
+
+        if name.is_empty() {
+            return Err(String::from("empty command"));
+        }
+
+        let name = name.replace('-', "_");
+        let name = name.as_str();
+
+        let cmd = match (name, arg) {
+            ("back", None) => Command::Back,
+            ("forward", None) => Command::Forward,
+            ("to_queue", None) => Command::ToQueue,
+            ("to_playlists", None) => Command::ToPlaylists,
+            ("toggle_top", None) => Command::ToggleTop,
+            ("search", query) => Command::Search(query.map(str::to_string)),
+            ("help", None) => Command::Help,
+            ("command_line", None) => Command::CommandLine,
+            ("focus", Some(blk)) => Command::Focus(blk.parse()?),
+            ("add_item_to_queue", None) => Command::AddItemToQueue,
+            ("play_item", None) => Command::PlayItem,
+            ("delete_item", None) => Command::DeleteItem,
+            ("move_down", None) => Command::MoveDown,
+            ("move_up", None) => Command::MoveUp,
+            ("jump_to_start", None) => Command::JumpToStart,
+            ("jump_to_end", None) => Command::JumpToEnd,
+            ("page_down", None) => Command::PageDown,
+            ("page_up", None) => Command::PageUp,
+            ("jump_to_album", None) => Command::JumpToAlbum,
+            ("jump_to_artist", None) => Command::JumpToArtist,
+            ("hover_up", None) => Command::HoverUp,
+            ("hover_down", None) => Command::HoverDown,
+            ("hover_left", None) => Command::HoverLeft,
+            ("hover_right", None) => Command::HoverRight,
+            ("select", None) => Command::Select,
+            ("cancel", None) => Command::Cancel,
+            ("move_left", None) => Command::MoveLeft,
+            ("move_right", None) => Command::MoveRight,
+            ("next_pane", None) => Command::NextPane,
+            ("delete_backward", None) => Command::DeleteBackward,
+            ("delete_word", None) => Command::DeleteWord,
+            ("clear_line", None) => Command::ClearLine,
+            ("drop_sort_key", None) => Command::DropSortKey,
+            ("move_item_up", None) => Command::MoveItemUp,
+            ("move_item_down", None) => Command::MoveItemDown,
+            ("play_next", None) => Command::PlayNext,
+            ("jump_to_playing", None) => Command::JumpToPlaying,
+            ("add_to_playlist", playlist) => Command::AddToPlaylist(playlist.map(str::to_string)),
+            ("ask_clear_queue", None) => Command::AskClearQueue,
+            ("complete", None) => Command::Complete,
+            ("clear_queue", None) => Command::ClearQueue,
+            ("crop_queue", None) => Command::CropQueue,
+            ("save_queue", Some(playlist)) => Command::SaveQueue(playlist.to_string()),
+            ("toggle_playback", None) => Command::TogglePlayback,
+            ("set_volume", Some(o)) => Command::SetVolume(parse_arg(name, o)?),
+            ("volume", Some(volume)) => Command::Volume(parse_arg(name, volume)?),
+            ("crossfade", Some(seconds)) => Command::Crossfade(parse_arg(name, seconds)?),
+            ("toggle_output", Some(output)) => Command::ToggleOutput(output.to_string()),
+            ("seek", Some(o)) => Command::Seek(parse_arg(name, o)?),
+            ("next_track", None) => Command::NextTrack,
+            ("previous_track", None) => Command::PreviousTrack,
+            ("shuffle", None) => Command::Shuffle,
+            ("repeat", None) => Command::Repeat,
+
+            ("focus" | "set_volume" | "seek" | "save_queue" | "volume" | "crossfade" | "toggle_output", None) => return Err(format!("`{}` expects an argument", name)),
+            (_, Some(arg)) if name.parse::<Command>().is_ok() => return Err(format!("`{}` does not take an argument, found `{}`", name, arg)),
+            _ => return Err(format!("unknown command `{}`", name)),
+        };
+
+        Ok(cmd)
+    }
+}
+
+// every command's name, for the command line to complete
+pub const NAMES: [&str; 53] = [
+    "back", "forward", "to_queue", "to_playlists", "toggle_top", "search", "help", "command_line", "focus",
+    "add_item_to_queue", "play_item", "delete_item", "move_down", "move_up", "page_down", "page_up",
+    "jump_to_start", "jump_to_end", "jump_to_album", "jump_to_artist", "hover_up", "hover_down", "hover_left",
+    "hover_right", "select", "cancel", "move_left", "move_right", "next_pane", "delete_backward", "delete_word", "clear_line",
+    "drop_sort_key", "move_item_up", "move_item_down", "play_next", "jump_to_playing", "add_to_playlist",
+    "ask_clear_queue", "complete", "clear_queue", "crop_queue", "save_queue", "toggle_playback", "set_volume",
+    "volume", "crossfade", "toggle_output", "seek", "next_track", "previous_track", "shuffle", "repeat",
+];
+
+fn parse_arg<T: std::str::FromStr>(name: &str, arg: &str) -> Result<T, String> {
+    arg.parse().map_err(|_| format!("invalid argument `{}` to `{}`", arg, name))
+}
+
+impl<'de> Deserialize<'de> for Command {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NAMES is kept by hand next to the parser, so this catches the two drifting apart
+    #[test]
+    fn names_parse_and_round_trip() {
+        for name in NAMES {
+            let cmd: Command = match name.parse() {
+                Ok(cmd) => cmd,
+                Err(_) => {
+                    let arg = if name == "focus" { "left_top" } else { "1" };
+                    format!("{} {}", name, arg).parse().unwrap_or_else(|e| panic!("`{}` doesn't parse: {}", name, e))
+                },
+            };
+
+            let written = cmd.to_string();
+            assert_eq!(written.split_whitespace().next(), Some(name));
+            assert_eq!(written.parse::<Command>(), Ok(cmd));
+        }
+
+        let mut names = NAMES.to_vec();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), NAMES.len());
+    }
+
+    #[test]
+    fn every_command_round_trips() {
+        let commands = [
+            Command::Back, Command::Forward, Command::ToQueue, Command::ToPlaylists, Command::ToggleTop,
+            Command::Search(None), Command::Search(Some(String::from("artist:björk -album:debut"))),
+            Command::Help, Command::CommandLine, Command::Focus(BlockKind::LeftTop), Command::Focus(BlockKind::Centre),
+            Command::AddItemToQueue, Command::PlayItem, Command::DeleteItem, Command::MoveDown, Command::MoveUp,
+            Command::PageDown, Command::PageUp, Command::JumpToStart, Command::JumpToEnd, Command::JumpToAlbum,
+            Command::JumpToArtist, Command::HoverUp, Command::HoverDown, Command::HoverLeft, Command::HoverRight,
+            Command::Select, Command::Cancel, Command::MoveLeft, Command::MoveRight, Command::NextPane,
+            Command::DeleteBackward, Command::DeleteWord, Command::ClearLine, Command::DropSortKey,
+            Command::MoveItemUp, Command::MoveItemDown, Command::PlayNext, Command::JumpToPlaying,
+            Command::AddToPlaylist(None), Command::AddToPlaylist(Some(String::from("Road Trip"))),
+            Command::AskClearQueue, Command::Complete, Command::ClearQueue, Command::CropQueue,
+            Command::SaveQueue(String::from("Road Trip")), Command::TogglePlayback,
+            Command::SetVolume(-5), Command::SetVolume(5), Command::Volume(40), Command::Crossfade(3),
+            Command::ToggleOutput(String::from("My ALSA Device")), Command::Seek(-10), Command::Seek(10),
+            Command::NextTrack, Command::PreviousTrack, Command::Shuffle, Command::Repeat,
+        ];
+
+        for cmd in &commands {
+            assert_eq!(cmd.to_string().parse::<Command>().as_ref(), Ok(cmd));
+        }
+
+        // every name is covered above
+        for name in NAMES {
+            assert!(commands.iter().any(|cmd| cmd.to_string().split_whitespace().next() == Some(name)), "`{}` isn't covered", name);
+        }
+    }
+}
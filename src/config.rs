@@ -0,0 +1,373 @@
+use serde::Deserialize;
+use anyhow::{ Context, Result, anyhow, bail };
+use std::path::{ Path, PathBuf };
+use std::collections::HashMap;
+use crate::theme::Theme;
+use crate::key::{ Binding, KeySequence, Keymap };
+use crate::collate;
+
+pub const DEFAULT_HOST: &str = "localhost";
+pub const DEFAULT_PORT: u16 = 6600;
+
+pub const USAGE: &str = "\
+usage: strofa [options]
+
+  -H, --host <host>          mpd's host, or the path to its socket
+  -p, --port <port>          mpd's port
+  -P, --password <password>  mpd's password
+  -s, --socket <path>        mpd's socket
+  -h, --help                 show this help
+";
+
+// anything left out of the config file keeps its default, so a config only
+// needs to mention what it changes
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub connection: ConnectionConfig,
+    pub theme: Theme,
+    // the global keymap
+    pub keys: HashMap<KeySequence, Binding>,
+    // the others, by name
+    pub keymaps: HashMap<Keymap, HashMap<KeySequence, Binding>>,
+    pub layout: LayoutConfig,
+    pub collation: CollationConfig,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LayoutConfig {
+    pub show_top: bool,
+    pub show_left: bool,
+    // percentage of the centre taken up by the library and playlists
+    pub left_width: u16,
+    // percentage of the left column taken up by the library
+    pub library_height: u16,
+    // rows kept in view above and below the selection while scrolling
+    pub scroll_off: usize,
+    // whether moving past the end of a list goes back to its start, and the other way round
+    pub wrap: bool,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            show_top: true,
+            show_left: true,
+            left_width: 20,
+            library_height: 30,
+            scroll_off: 2,
+            wrap: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CollationConfig {
+    // left out when ordering names, so "The Beatles" is listed under B
+    pub articles: Vec<String>,
+}
+
+impl Default for CollationConfig {
+    fn default() -> Self {
+        Self {
+            articles: collate::DEFAULT_ARTICLES.iter().map(|article| article.to_string()).collect(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        Some(xdg_dir("XDG_CONFIG_HOME", ".config")?.join("config.zmr"))
+    }
+
+    pub fn load() -> Result<Self> {
+        match Self::path() {
+            Some(path) if path.exists() => Self::from_file(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+
+        zmerald::from_str(&contents).map_err(|e| {
+            let line = contents.lines().nth(e.position.line.saturating_sub(1)).unwrap_or_default();
+            anyhow!(
+                "{}:{}:{}: {}\n{:>5} | {}\n      | {}^",
+                path.display(), e.position.line, e.position.col, e.code,
+                e.position.line, line, " ".repeat(e.position.col.saturating_sub(1))
+            )
+        })
+    }
+}
+
+// every field is optional so that the config file, the environment and the
+// command line can each fill in only what they know about
+#[derive(Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ConnectionConfig {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub password: Option<String>,
+    pub socket: Option<PathBuf>,
+}
+
+// what the command line asked for
+#[derive(Debug, Clone, PartialEq)]
+pub enum Args {
+    Connect(ConnectionConfig),
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Address {
+    Tcp(String, u16),
+    Socket(PathBuf),
+}
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Address::Tcp(host, port) => write!(f, "{}:{}", host, port),
+            Address::Socket(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl ConnectionConfig {
+    // `MPD_HOST` follows the libmpdclient convention of `password@host`, where
+    // host may also be the path to a unix socket
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(std::env::var("MPD_HOST").ok(), std::env::var("MPD_PORT").ok())
+    }
+
+    fn from_vars(mpd_host: Option<String>, mpd_port: Option<String>) -> Result<Self> {
+        let mut config = Self::default();
+
+        if let Some(value) = mpd_host {
+            let host = match value.rsplit_once('@') {
+                Some((password, host)) if !password.is_empty() => {
+                    config.password = Some(password.to_string());
+                    host.to_string()
+                },
+
+                _ => value,
+            };
+
+            if host.starts_with('@') {
+                bail!("abstract sockets are not supported (MPD_HOST={})", host);
+            }
+
+            if is_path(&host) {
+                config.socket = Some(expand_home(&host));
+            } else if !host.is_empty() {
+                config.host = Some(host);
+            }
+        }
+
+        if let Some(value) = mpd_port {
+            config.port = Some(value.parse().with_context(|| format!("invalid MPD_PORT: {}", value))?);
+        }
+
+        Ok(config)
+    }
+
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Args> {
+        let mut config = Self::default();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+
+            let mut value = || match inline.clone().or_else(|| args.next()) {
+                Some(value) => Ok(value),
+                None => Err(anyhow!("missing value for {}", flag)),
+            };
+
+            match flag.as_str() {
+                "-h" | "--help" => return Ok(Args::Help),
+
+                "-H" | "--host" => {
+                    let host = value()?;
+                    if is_path(&host) {
+                        config.socket = Some(expand_home(&host));
+                    } else {
+                        config.host = Some(host);
+                    }
+                },
+
+                "-p" | "--port" => {
+                    let port = value()?;
+                    config.port = Some(port.parse().with_context(|| format!("invalid port: {}", port))?);
+                },
+
+                "-P" | "--password" => config.password = Some(value()?),
+                "-s" | "--socket" => config.socket = Some(expand_home(&value()?)),
+                _ => bail!("unknown argument: {}", flag),
+            }
+        }
+
+        Ok(Args::Connect(config))
+    }
+
+    // fields set in `other` take precedence over the ones in `self`
+    pub fn merge(self, other: Self) -> Self {
+        // an explicit host overrides a socket from a lower layer and vice versa
+        let (host, socket) = match (&other.host, &other.socket) {
+            (None, None) => (self.host, self.socket),
+            _ => (other.host, other.socket),
+        };
+
+        Self {
+            host,
+            socket,
+            port: other.port.or(self.port),
+            password: other.password.or(self.password),
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        if let Some(socket) = &self.socket {
+            return Address::Socket(socket.clone());
+        }
+
+        if let Some(host) = &self.host {
+            return Address::Tcp(host.clone(), self.port.unwrap_or(DEFAULT_PORT));
+        }
+
+        // mpd's default socket location, only used when nothing was specified
+        if self.port.is_none() {
+            if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+                let socket = PathBuf::from(runtime_dir).join("mpd").join("socket");
+                if socket.exists() {
+                    return Address::Socket(socket);
+                }
+            }
+        }
+
+        Address::Tcp(DEFAULT_HOST.to_string(), self.port.unwrap_or(DEFAULT_PORT))
+    }
+}
+
+// strofa's directory under one of the xdg base directories, eg.
+// `$XDG_STATE_HOME/strofa`, or under `fallback` in the home directory
+pub fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    let base = match std::env::var_os(var) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(fallback),
+    };
+
+    Some(base.join("strofa"))
+}
+
+fn is_path(host: &str) -> bool {
+    host.starts_with('/') || host.starts_with('~')
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(host: Option<&str>, port: Option<&str>) -> Result<ConnectionConfig> {
+        ConnectionConfig::from_vars(host.map(str::to_string), port.map(str::to_string))
+    }
+
+    fn args(args: &[&str]) -> Result<Args> {
+        ConnectionConfig::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn mpd_host_with_password() {
+        let config = vars(Some("secret@music.local"), None).unwrap();
+        assert_eq!(config.password.as_deref(), Some("secret"));
+        assert_eq!(config.host.as_deref(), Some("music.local"));
+
+        // only the last `@` separates the password
+        let config = vars(Some("p@ss@music.local"), None).unwrap();
+        assert_eq!(config.password.as_deref(), Some("p@ss"));
+        assert_eq!(config.host.as_deref(), Some("music.local"));
+
+        let config = vars(Some("music.local"), None).unwrap();
+        assert_eq!(config.password, None);
+    }
+
+    #[test]
+    fn mpd_host_socket() {
+        let config = vars(Some("secret@/run/mpd/socket"), None).unwrap();
+        assert_eq!(config.socket, Some(PathBuf::from("/run/mpd/socket")));
+        assert_eq!(config.host, None);
+        assert_eq!(config.address(), Address::Socket(PathBuf::from("/run/mpd/socket")));
+
+        assert!(vars(Some("@abstract"), None).is_err());
+    }
+
+    #[test]
+    fn mpd_port() {
+        let config = vars(Some("music.local"), Some("6601")).unwrap();
+        assert_eq!(config.address(), Address::Tcp(String::from("music.local"), 6601));
+        assert!(vars(None, Some("sixty")).is_err());
+    }
+
+    #[test]
+    fn command_line() {
+        let expected = ConnectionConfig {
+            host: Some(String::from("music.local")),
+            port: Some(6601),
+            password: Some(String::from("secret")),
+            socket: None,
+        };
+
+        assert_eq!(args(&["-H", "music.local", "--port=6601", "-P", "secret"]).unwrap(), Args::Connect(expected));
+        assert_eq!(args(&["--host", "/run/mpd/socket"]).unwrap(), Args::Connect(ConnectionConfig {
+            socket: Some(PathBuf::from("/run/mpd/socket")),
+            ..ConnectionConfig::default()
+        }));
+
+        assert_eq!(args(&["-p", "1", "--help"]).unwrap(), Args::Help);
+        assert!(args(&["--port"]).is_err());
+        assert!(args(&["--bogus"]).is_err());
+    }
+
+    #[test]
+    fn merge_precedence() {
+        let file = ConnectionConfig {
+            host: Some(String::from("file.local")),
+            port: Some(6600),
+            password: Some(String::from("file")),
+            socket: None,
+        };
+
+        let env = ConnectionConfig {
+            password: Some(String::from("env")),
+            ..ConnectionConfig::default()
+        };
+
+        let socket = ConnectionConfig {
+            socket: Some(PathBuf::from("/run/mpd/socket")),
+            ..ConnectionConfig::default()
+        };
+
+        let merged = file.clone().merge(env);
+        assert_eq!(merged.host.as_deref(), Some("file.local"));
+        assert_eq!(merged.port, Some(6600));
+        assert_eq!(merged.password.as_deref(), Some("env"));
+
+        // a socket given later replaces the host rather than losing to it
+        let merged = file.merge(socket);
+        assert_eq!(merged.host, None);
+        assert_eq!(merged.address(), Address::Socket(PathBuf::from("/run/mpd/socket")));
+        assert_eq!(merged.password.as_deref(), Some("file"));
+    }
+}
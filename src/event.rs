@@ -1,32 +1,147 @@
-use std::time::Duration;
-use crossterm::event;
+use std::time::{ Duration, Instant };
+use crossterm::event::{ self, EventStream };
+use futures_util::StreamExt;
+use mpd_client::{ Subsystem, state_changes::StateChanges };
+use tokio::time::{ self, Interval, MissedTickBehavior };
+use tokio::sync::mpsc::{ self, UnboundedReceiver, UnboundedSender };
+use anyhow::{ Result, bail };
+use serde::{ Deserialize, Deserializer, de };
+use crate::block::{ Found, MainBlock, Playbar };
+use mpd_client::commands::responses::Playlist;
+use crate::index::LibraryIndex;
+
+// a second click on the same cell within this long is a double click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
 
-pub struct Events;
 pub enum Event {
     Input(Key),
+    Mouse(Mouse),
+    Resize,
+    Tick,
+    StateChange(Subsystem),
+    // the generation and query of a live search, and what it found
+    SearchResults(u64, String, Result<Found>),
+    LibraryIndexed(Result<LibraryIndex>),
+    // how many of the library's songs have been listed, out of how many
+    IndexProgress(usize, usize),
+    // a main view fetched in the background, under the generation it was asked for with
+    ViewLoaded(u64, Result<MainBlock>),
+    // how many songs of a view being loaded have been listed, out of how many
+    ViewProgress(u64, usize, usize),
+    // the playbar and the playlists fetched again after mpd reported them
+    // changing, under the generation of the fetch
+    PlaybarFetched(u64, Result<Playbar>),
+    PlaylistsFetched(u64, Result<Vec<Playlist>>),
 }
 
-use futures_util::Stream;
+// merges terminal input, mpd's idle notifications, a periodic tick and
+// whatever tasks spawned by the ui send back into a single stream of events,
+// none of them blocking the others
+pub struct Events {
+    terminal: EventStream,
+    state_changes: StateChanges,
+    tick: Interval,
+    sender: UnboundedSender<Event>,
+    receiver: UnboundedReceiver<Event>,
+    // when and where the left button was last pressed, terminals only report single clicks
+    last_click: Option<(Instant, u16, u16)>,
+}
 
 impl Events {
-    pub fn new() -> impl Stream<Item = Event> {
-        async_stream::stream! {
-            loop {
-                if event::poll(Duration::from_millis(250)).unwrap() {
-                    if let event::Event::Key(key) = event::read().unwrap() {
-                        yield Event::Input(Key::from(key))
-                    }
-                }
+    pub fn new(state_changes: StateChanges, tick_rate: Duration) -> Self {
+        let mut tick = time::interval(tick_rate);
+        tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        Self {
+            terminal: EventStream::new(),
+            state_changes,
+            tick,
+            sender,
+            receiver,
+            last_click: None,
+        }
+    }
+
+    pub fn sender(&self) -> UnboundedSender<Event> {
+        self.sender.clone()
+    }
+
+    pub async fn next(&mut self) -> Result<Event> {
+        loop {
+            tokio::select! {
+                event = self.terminal.next() => match event {
+                    Some(Ok(event::Event::Key(key))) => return Ok(Event::Input(Key::from(key))),
+                    Some(Ok(event::Event::Resize(_, _))) => return Ok(Event::Resize),
+                    Some(Ok(event::Event::Mouse(mouse))) => match self.mouse(mouse) {
+                        Some(mouse) => return Ok(Event::Mouse(mouse)),
+                        None => continue,
+                    },
+
+                    Some(Err(e)) => return Err(e.into()),
+                    None => bail!("terminal event stream closed"),
+                },
+
+                change = self.state_changes.next() => match change {
+                    Some(subsystem) => return Ok(Event::StateChange(subsystem?)),
+                    None => bail!("connection to mpd closed"),
+                },
+
+                _ = self.tick.tick() => return Ok(Event::Tick),
+
+                // never closes, as a sender is kept in `self`
+                Some(event) = self.receiver.recv() => return Ok(event),
             }
         }
     }
+
+    // only the left button and the wheel do anything, moving and dragging are dropped
+    fn mouse(&mut self, mouse: event::MouseEvent) -> Option<Mouse> {
+        let (column, row) = (mouse.column, mouse.row);
+        let kind = match mouse.kind {
+            event::MouseEventKind::ScrollUp => MouseKind::ScrollUp,
+            event::MouseEventKind::ScrollDown => MouseKind::ScrollDown,
+            event::MouseEventKind::Down(event::MouseButton::Left) => match self.last_click.take() {
+                Some((at, c, r)) if at.elapsed() < DOUBLE_CLICK && (c, r) == (column, row) => MouseKind::DoubleClick,
+                _ => {
+                    self.last_click = Some((Instant::now(), column, row));
+                    MouseKind::Click
+                },
+            },
+
+            _ => return None,
+        };
+
+        Some(Mouse { kind, column, row })
+    }
 }
 
-// normalise stream above, do I need async_stream ?
-// todo: use crossterm::KeyEvent instead of this abstraction.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Mouse {
+    pub kind: MouseKind,
+    pub column: u16,
+    pub row: u16,
+}
 
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum MouseKind {
+    Click,
+    // sent instead of a second click
+    DoubleClick,
+    ScrollUp,
+    ScrollDown,
+}
+
+// a key along with the modifiers held down, which is what bindings are made of
 #[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
-pub enum Key {
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: Modifiers,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub enum KeyCode {
     Enter,
     Tab,
     Backspace,
@@ -35,45 +150,268 @@ pub enum Key {
     Right,
     Up,
     Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Delete,
+    Insert,
+    F(u8),
     Char(char),
-    Ctrl(char),
-    Alt(char),
     Unknown
 }
 
-impl std::fmt::Display for Key {
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Default)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers(0);
+    pub const CTRL: Modifiers = Modifiers(1);
+    pub const ALT: Modifiers = Modifiers(1 << 1);
+    pub const SHIFT: Modifiers = Modifiers(1 << 2);
+
+    pub fn contains(self, other: Modifiers) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn remove(&mut self, other: Modifiers) {
+        self.0 &= !other.0;
+    }
+}
+
+impl std::ops::BitOr for Modifiers {
+    type Output = Modifiers;
+
+    fn bitor(self, other: Modifiers) -> Modifiers {
+        Modifiers(self.0 | other.0)
+    }
+}
+
+impl std::ops::BitOrAssign for Modifiers {
+    fn bitor_assign(&mut self, other: Modifiers) {
+        self.0 |= other.0;
+    }
+}
+
+// a key as a value or a pattern: `key!(Up)`, `key!('q')`, `key!(Char(c))`,
+// `key!(F(5))` or with a single modifier, `key!(Ctrl + 'w')`
+macro_rules! key {
+    (Ctrl + $($code:tt)+) => { $crate::event::Key { code: $crate::event::key!(@code $($code)+), modifiers: $crate::event::Modifiers::CTRL } };
+    (Alt + $($code:tt)+) => { $crate::event::Key { code: $crate::event::key!(@code $($code)+), modifiers: $crate::event::Modifiers::ALT } };
+    (Shift + $($code:tt)+) => { $crate::event::Key { code: $crate::event::key!(@code $($code)+), modifiers: $crate::event::Modifiers::SHIFT } };
+    (@code $c:literal) => { $crate::event::KeyCode::Char($c) };
+    (@code $code:ident ($($arg:tt)*)) => { $crate::event::KeyCode::$code($($arg)*) };
+    (@code $code:ident) => { $crate::event::KeyCode::$code };
+    ($($code:tt)+) => { $crate::event::Key { code: $crate::event::key!(@code $($code)+), modifiers: $crate::event::Modifiers::NONE } };
+}
+
+pub(crate) use key;
+
+impl Key {
+    // shift only changes which character a key types, so `<S-a>` and `A` are the same key
+    pub fn new(code: KeyCode, mut modifiers: Modifiers) -> Self {
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(Modifiers::SHIFT) => {
+                modifiers.remove(Modifiers::SHIFT);
+                KeyCode::Char(c.to_uppercase().next().unwrap_or(c))
+            },
+
+            code => code,
+        };
+
+        Self { code, modifiers }
+    }
+}
+
+impl std::fmt::Display for KeyCode {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match *self {
-            Key::Alt(' ') => write!(f, "<Alt+Space>"),
-            Key::Ctrl(' ') => write!(f, "<Ctrl+Space>"),
-            Key::Char(' ') => write!(f, "<Space>"),
-            Key::Alt(c) => write!(f, "<Alt+{}>", c),
-            Key::Ctrl(c) => write!(f, "<Ctrl+{}>", c),
-            Key::Char(c) => write!(f, "{}", c),
-            Key::Left | Key::Right | Key::Up | Key::Down => write!(f, "<{:?} Arrow Key>", self),
-            Key::Enter | Key::Tab | Key::Backspace | Key::Esc => write!(f, "<{:?}>", self),
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
             _ => write!(f, "{:?}", self),
         }
     }
 }
 
+// vim's notation, eg. `q`, `<Space>`, `<C-w>` or `<C-S-Left>`
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.code {
+            KeyCode::Char(c) if c != ' ' && self.modifiers == Modifiers::NONE => write!(f, "{}", c),
+            _ => {
+                let prefixes = [(Modifiers::CTRL, "C-"), (Modifiers::ALT, "A-"), (Modifiers::SHIFT, "S-")];
+                write!(f, "<")?;
+                for (modifier, prefix) in prefixes {
+                    if self.modifiers.contains(modifier) {
+                        write!(f, "{}", prefix)?;
+                    }
+                }
+
+                write!(f, "{}>", self.code)
+            },
+        }
+    }
+}
+
+// accepts everything `Display` produces, so keys can be written in the config
+// file the same way they are shown in the ui, as well as the older
+// `<Ctrl+b>` and `<Up Arrow Key>`
+impl std::str::FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Ok(Key::new(KeyCode::Char(c), Modifiers::NONE));
+        }
+
+        let unknown = || format!("unknown key `{}`", s);
+        let mut rest = s.strip_prefix('<')
+            .and_then(|s| s.strip_suffix('>'))
+            .ok_or_else(unknown)?
+            .trim_end_matches(" Arrow Key");
+
+        // a separator right at the end is the key itself, as in `<C-->`
+        let mut modifiers = Modifiers::NONE;
+        while let Some((prefix, after)) = rest.split_once(['-', '+']).filter(|(_, after)| !after.is_empty()) {
+            modifiers |= match prefix {
+                "C" | "Ctrl" => Modifiers::CTRL,
+                "A" | "M" | "Alt" => Modifiers::ALT,
+                "S" | "Shift" => Modifiers::SHIFT,
+                _ => break,
+            };
+
+            rest = after;
+        }
+
+        let mut chars = rest.chars();
+        let code = match rest {
+            "Space" => KeyCode::Char(' '),
+            "Enter" => KeyCode::Enter,
+            "Tab" => KeyCode::Tab,
+            "Backspace" => KeyCode::Backspace,
+            "Esc" => KeyCode::Esc,
+            "Left" => KeyCode::Left,
+            "Right" => KeyCode::Right,
+            "Up" => KeyCode::Up,
+            "Down" => KeyCode::Down,
+            "Home" => KeyCode::Home,
+            "End" => KeyCode::End,
+            "PageUp" => KeyCode::PageUp,
+            "PageDown" => KeyCode::PageDown,
+            "Delete" | "Del" => KeyCode::Delete,
+            "Insert" | "Ins" => KeyCode::Insert,
+            _ => match rest.strip_prefix('F').and_then(|n| n.parse().ok()).filter(|n| (1..=24).contains(n)) {
+                Some(n) => KeyCode::F(n),
+                None => match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return Err(unknown()),
+                },
+            },
+        };
+
+        // shift with anything but a letter types another character, which
+        // terminals send instead, so `<S-->` could never be pressed
+        match code {
+            KeyCode::Char(c) if modifiers.contains(Modifiers::SHIFT) && !c.is_alphabetic() => {
+                Err(format!("`{}` can't be shifted, write the character shift types instead", s))
+            },
+
+            _ => Ok(Key::new(code, modifiers)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
+}
+
 impl From<event::KeyEvent> for Key {
     fn from(key_event: event::KeyEvent) -> Self {
-        match key_event {    
-            event::KeyEvent { code: event::KeyCode::Esc, .. } => Key::Esc,
-            event::KeyEvent { code: event::KeyCode::Backspace, .. } => Key::Backspace,
-            event::KeyEvent { code: event::KeyCode::Left, .. } => Key::Left,
-            event::KeyEvent { code: event::KeyCode::Right, .. } => Key::Right,
-            event::KeyEvent { code: event::KeyCode::Up, .. } => Key::Up,
-            event::KeyEvent { code: event::KeyCode::Down, .. } => Key::Down,
-            event::KeyEvent { code: event::KeyCode::Enter, .. } => Key::Enter,
-            event::KeyEvent { code: event::KeyCode::Tab, .. } => Key::Tab,
-
-            // First check for char + modifier
-            event::KeyEvent { code: event::KeyCode::Char(c), modifiers: event::KeyModifiers::ALT } => Key::Alt(c),
-            event::KeyEvent { code: event::KeyCode::Char(c), modifiers: event::KeyModifiers::CONTROL } => Key::Ctrl(c),
-            event::KeyEvent { code: event::KeyCode::Char(c), .. } => Key::Char(c),
-            _ => Key::Unknown,
+        let mut modifiers = Modifiers::NONE;
+        for (modifier, ours) in [
+            (event::KeyModifiers::CONTROL, Modifiers::CTRL),
+            (event::KeyModifiers::ALT, Modifiers::ALT),
+            (event::KeyModifiers::SHIFT, Modifiers::SHIFT),
+        ] {
+            if key_event.modifiers.contains(modifier) {
+                modifiers |= ours;
+            }
         }
+
+        let code = match key_event.code {
+            event::KeyCode::Esc => KeyCode::Esc,
+            event::KeyCode::Backspace => KeyCode::Backspace,
+            event::KeyCode::Left => KeyCode::Left,
+            event::KeyCode::Right => KeyCode::Right,
+            event::KeyCode::Up => KeyCode::Up,
+            event::KeyCode::Down => KeyCode::Down,
+            event::KeyCode::Enter => KeyCode::Enter,
+            event::KeyCode::Tab => KeyCode::Tab,
+            // what terminals send for shift and tab
+            event::KeyCode::BackTab => {
+                modifiers |= Modifiers::SHIFT;
+                KeyCode::Tab
+            },
+
+            event::KeyCode::Home => KeyCode::Home,
+            event::KeyCode::End => KeyCode::End,
+            event::KeyCode::PageUp => KeyCode::PageUp,
+            event::KeyCode::PageDown => KeyCode::PageDown,
+            event::KeyCode::Delete => KeyCode::Delete,
+            event::KeyCode::Insert => KeyCode::Insert,
+            event::KeyCode::F(n) => KeyCode::F(n),
+            event::KeyCode::Char(c) => KeyCode::Char(c),
+            _ => KeyCode::Unknown,
+        };
+
+        Key::new(code, modifiers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(s: &str) -> Key {
+        let key: Key = s.parse().unwrap_or_else(|e| panic!("`{}` failed: {}", s, e));
+        assert_eq!(key.to_string(), s);
+        key
+    }
+
+    #[test]
+    fn keys_round_trip() {
+        assert_eq!(round_trip("<C-S-Left>"), Key { code: KeyCode::Left, modifiers: Modifiers::CTRL | Modifiers::SHIFT });
+        assert_eq!(round_trip("<C-->"), key!(Ctrl + '-'));
+        assert_eq!(round_trip("<C->>"), key!(Ctrl + '>'));
+        assert_eq!(round_trip("<Space>"), key!(' '));
+        assert_eq!(round_trip("<F24>"), key!(F(24)));
+        assert_eq!(round_trip("<A-Enter>"), key!(Alt + Enter));
+        assert_eq!(round_trip("q"), key!('q'));
+    }
+
+    #[test]
+    fn older_forms() {
+        assert_eq!("<Ctrl+b>".parse(), Ok(key!(Ctrl + 'b')));
+        assert_eq!("<Up Arrow Key>".parse(), Ok(key!(Up)));
+        assert_eq!("<Del>".parse(), Ok(key!(Delete)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn shift() {
+        assert_eq!("<S-a>".parse(), Ok(key!('A')));
+        assert_eq!("<S-Tab>".parse(), Ok(key!(Shift + Tab)));
+        assert!("<S-->".parse::<Key>().is_err());
+        assert!("<C-S-1>".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn unknown_keys() {
+        assert!("<Hyper-x>".parse::<Key>().is_err());
+        assert!("<F25>".parse::<Key>().is_err());
+        assert!("ab".parse::<Key>().is_err());
+    }
+}
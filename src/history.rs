@@ -0,0 +1,200 @@
+use crate::block::{ AlbumKind, TrackKind };
+use mpd_client::Subsystem;
+
+const MAX_HISTORY: usize = 50;
+
+// what a main view shows, enough to fetch it again when navigating back to it
+#[derive(Clone, PartialEq, Debug)]
+pub enum View {
+    Queue,
+    Search(String),
+    Artists,
+    Albums(AlbumKind),
+    Tracks(TrackKind),
+}
+
+impl View {
+    // how the view appears in a breadcrumb following its parent
+    pub fn label(&self) -> &str {
+        match self {
+            View::Queue => "Queue",
+            View::Search(query) => query,
+            View::Artists => "Artists",
+            View::Albums(AlbumKind::All) => "Albums",
+            View::Albums(AlbumKind::Artist(artist)) => artist,
+            View::Tracks(TrackKind::All) => "Tracks",
+            View::Tracks(TrackKind::Album(_, album)) => album,
+            View::Tracks(TrackKind::Artist(artist)) => artist,
+            View::Tracks(TrackKind::Playlist(name)) => name,
+        }
+    }
+
+    // each view is only fetched again for the subsystems its contents depend on
+    pub fn depends_on(&self, subsystem: &Subsystem) -> bool {
+        match (self, subsystem) {
+            (View::Queue, Subsystem::Queue) => true,
+            // database changes are picked up once the library index is rebuilt
+            (View::Search(_), Subsystem::StoredPlaylist) => true,
+            (View::Artists | View::Albums(_), Subsystem::Database) => true,
+            (View::Tracks(kind), Subsystem::StoredPlaylist) => kind.is_playlist(),
+            (View::Tracks(kind), Subsystem::Database) => !kind.is_playlist(),
+            _ => false,
+        }
+    }
+
+    // how the view is introduced when nothing before it is part of the breadcrumb
+    fn root(&self) -> Option<&str> {
+        match self {
+            View::Search(_) => Some("Search"),
+            View::Albums(AlbumKind::Artist(_)) => Some("Albums"),
+            View::Tracks(TrackKind::Album(..)) => Some("Album"),
+            View::Tracks(TrackKind::Artist(_)) => Some("Artist"),
+            View::Tracks(TrackKind::Playlist(_)) => Some("Playlist"),
+            _ => None,
+        }
+    }
+
+    fn is_parent_of(&self, child: &View) -> bool {
+        match (self, child) {
+            (View::Artists, View::Albums(AlbumKind::Artist(_))) => true,
+            (View::Albums(AlbumKind::Artist(artist)), View::Tracks(TrackKind::Album(album_artist, _))) => artist == album_artist,
+            (View::Albums(AlbumKind::All), View::Tracks(TrackKind::Album(..))) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Location {
+    pub view: View,
+    pub index: usize,
+    // the first row shown, so the view comes back at the same place on the screen
+    pub offset: usize,
+}
+
+impl Location {
+    // a view opened afresh, at its first row
+    pub fn new(view: View) -> Self {
+        Self { view, index: 0, offset: 0 }
+    }
+}
+
+// a browser-like back/forward stack of the main views visited
+#[derive(Clone, Default)]
+pub struct History {
+    back: Vec<Location>,
+    forward: Vec<Location>,
+}
+
+impl History {
+    // visiting a new view discards whatever could have been gone forward to
+    pub fn push(&mut self, location: Location) {
+        if self.back.len() == MAX_HISTORY {
+            self.back.remove(0);
+        }
+
+        self.back.push(location);
+        self.forward.clear();
+    }
+
+    // moves one step back, remembering `current` so it can be gone forward to
+    pub fn back(&mut self, current: Location) -> Option<Location> {
+        let location = self.back.pop()?;
+        self.forward.push(current);
+        Some(location)
+    }
+
+    pub fn forward(&mut self, current: Location) -> Option<Location> {
+        let location = self.forward.pop()?;
+        self.back.push(current);
+        Some(location)
+    }
+
+    // eg. `Artists › Björk › Homogenic`, following the views that led to `current`
+    pub fn breadcrumb(&self, current: &View) -> String {
+        let mut crumbs = vec![current.label()];
+        let mut child = current;
+
+        for location in self.back.iter().rev() {
+            if !location.view.is_parent_of(child) {
+                break;
+            }
+
+            crumbs.push(location.view.label());
+            child = &location.view;
+        }
+
+        if let Some(root) = child.root() {
+            crumbs.push(root);
+        }
+
+        crumbs.reverse();
+        format!(" {} ", crumbs.join(" › "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(view: View) -> Location {
+        Location::new(view)
+    }
+
+    fn artist(name: &str) -> View {
+        View::Albums(AlbumKind::Artist(name.to_string()))
+    }
+
+    fn album(artist: &str, name: &str) -> View {
+        View::Tracks(TrackKind::Album(artist.to_string(), name.to_string()))
+    }
+
+    #[test]
+    fn back_and_forward() {
+        let mut history = History::default();
+        history.push(at(View::Queue));
+        history.push(at(View::Artists));
+
+        // showing the albums of an artist
+        let back = history.back(at(artist("Björk"))).unwrap();
+        assert_eq!(back.view, View::Artists);
+        let back = history.back(back).unwrap();
+        assert_eq!(back.view, View::Queue);
+        assert!(history.back(at(View::Queue)).is_none());
+
+        let forward = history.forward(back).unwrap();
+        assert_eq!(forward.view, View::Artists);
+        let forward = history.forward(forward).unwrap();
+        assert_eq!(forward.view, artist("Björk"));
+        assert!(history.forward(at(artist("Björk"))).is_none());
+    }
+
+    #[test]
+    fn visiting_truncates() {
+        let mut history = History::default();
+        history.push(at(View::Queue));
+        history.back(at(View::Artists));
+        history.push(at(View::Queue));
+        assert!(history.forward(at(View::Artists)).is_none());
+
+        for _ in 0..MAX_HISTORY + 10 {
+            history.push(at(View::Artists));
+        }
+
+        assert_eq!(history.back.len(), MAX_HISTORY);
+    }
+
+    #[test]
+    fn breadcrumb() {
+        let mut history = History::default();
+        history.push(at(View::Queue));
+        history.push(at(View::Artists));
+        history.push(at(artist("Björk")));
+        assert_eq!(history.breadcrumb(&album("Björk", "Homogenic")), " Artists › Björk › Homogenic ");
+
+        // only the views leading to the current one
+        assert_eq!(history.breadcrumb(&album("Blur", "Parklife")), " Album › Parklife ");
+        assert_eq!(history.breadcrumb(&View::Search(String::from("homo"))), " Search › homo ");
+        assert_eq!(History::default().breadcrumb(&View::Queue), " Queue ");
+    }
+}
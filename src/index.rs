@@ -0,0 +1,394 @@
+use crate::event::Event;
+use crate::cache::{ self, Cached };
+use crate::client::{ StrofaClient, Progress };
+use crate::query::Term;
+use mpd_client::{ Client, commands, commands::responses::Song, filter::Filter, tag::Tag };
+use std::collections::HashSet;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+use anyhow::Result;
+
+// listing every match of a one letter query would only bury the good ones
+const MAX_RESULTS: usize = 1000;
+
+// songs indexed between letting the ui carry on
+const BATCH: usize = 5000;
+
+// new songs fetched one at a time when updating the cache, past which
+// listing everything again is quicker
+const MAX_ADDED: usize = 500;
+
+// the whole library kept in memory, so searching neither waits on mpd nor
+// needs exact spelling
+#[derive(Default)]
+pub struct LibraryIndex {
+    entries: Vec<Entry>,
+    loaded: bool,
+}
+
+// a song along with its searchable tags, already case folded
+struct Entry {
+    song: Song,
+    title: String,
+    artist: String,
+    album_artist: String,
+    album: String,
+    genre: String,
+    date: String,
+    composer: String,
+    file: String,
+}
+
+impl Entry {
+    fn new(song: Song) -> Self {
+        let tag = |tag: Tag| song.tags.get(&tag).map(|values| values.join(" ").to_lowercase()).unwrap_or_default();
+
+        Self {
+            title: tag(Tag::Title),
+            artist: tag(Tag::Artist),
+            album_artist: tag(Tag::AlbumArtist),
+            album: tag(Tag::Album),
+            genre: tag(Tag::Genre),
+            date: tag(Tag::Date),
+            composer: tag(Tag::Composer),
+            file: song.url.to_lowercase(),
+            song,
+        }
+    }
+
+    // the fields a term looks at, with how much a match in each counts out of 10
+    fn fields(&self, tag: &Option<Tag>) -> Vec<(&str, i64)> {
+        match tag {
+            None => vec![(&self.title, 10), (&self.artist, 9), (&self.album_artist, 9), (&self.album, 9), (&self.file, 5)],
+            Some(Tag::Title) => vec![(&self.title, 10)],
+            Some(Tag::Artist) => vec![(&self.artist, 10), (&self.album_artist, 9)],
+            Some(Tag::AlbumArtist) => vec![(&self.album_artist, 10)],
+            Some(Tag::Album) => vec![(&self.album, 10)],
+            Some(Tag::Genre) => vec![(&self.genre, 10)],
+            Some(Tag::Date) => vec![(&self.date, 10)],
+            Some(Tag::Composer) => vec![(&self.composer, 10)],
+            Some(_) => vec![(&self.file, 10)],
+        }
+    }
+}
+
+impl LibraryIndex {
+    // from the cache, brought up to date with whatever changed since it was
+    // written, or else listed in full, reporting how many songs are listed
+    pub async fn build(client: &Client, host: &str, progress: &Progress<'_>) -> Result<Self> {
+        let db_update = client.command(commands::Stats).await?.db_last_update;
+
+        // reading and writing the cache go on a thread of their own, as the ui shares this one
+        let cached = {
+            let host = host.to_string();
+            tokio::task::spawn_blocking(move || cache::load(&host, db_update)).await?
+        };
+
+        let songs = match cached {
+            Some(Cached::Current(songs)) => songs,
+            stale => {
+                let songs = match stale {
+                    Some(Cached::Stale(since, songs)) => match LibraryIndex::update(client, songs, since).await? {
+                        Some(songs) => songs,
+                        None => client.list_library(progress).await?,
+                    },
+
+                    _ => client.list_library(progress).await?,
+                };
+
+                let host = host.to_string();
+                tokio::task::spawn_blocking(move || {
+                    cache::save(&host, db_update, &songs);
+                    songs
+                }).await?
+            },
+        };
+
+        let total = songs.len();
+        let mut songs = songs.into_iter();
+        let mut entries = Vec::with_capacity(total);
+
+        loop {
+            entries.extend(songs.by_ref().take(BATCH).map(Entry::new));
+            if entries.len() == total {
+                break;
+            }
+
+            // the ui shares the thread, and carries on between batches
+            tokio::task::yield_now().await;
+        }
+
+        Ok(Self { entries, loaded: true })
+    }
+
+    // brings songs cached as of the database update at `since` up to date:
+    // drops the files mpd no longer lists, and fetches the ones modified
+    // since along with any new ones, which keep their old modification time
+    // when moved in; none when there are too many of those to be worth it
+    async fn update(client: &Client, mut songs: Vec<Song>, since: u64) -> Result<Option<Vec<Song>>> {
+        let files: HashSet<String> = client.command(commands::List::new(Tag::Other("file".into()))).await?
+            .fields
+            .into_iter()
+            .map(|(_, file)| file)
+            .collect();
+
+        let modified = client.modified_since(since).await?;
+        let refetched: HashSet<&str> = modified.iter().map(|song| song.url.as_str()).collect();
+        songs.retain(|song| files.contains(&song.url) && !refetched.contains(song.url.as_str()));
+
+        let known: HashSet<&str> = songs.iter().chain(&modified).map(|song| song.url.as_str()).collect();
+        let added: Vec<String> = files.iter().filter(|file| !known.contains(file.as_str())).cloned().collect();
+        if added.len() > MAX_ADDED {
+            return Ok(None);
+        }
+
+        songs.extend(modified);
+        for file in added {
+            songs.extend(client.command(commands::Find::new(Filter::tag(Tag::Other("file".into()), file))).await?);
+        }
+
+        Ok(Some(songs))
+    }
+
+    // builds the index in the background, sending it to the event loop once done
+    pub fn rebuild(client: &Client, host: &str, events: &UnboundedSender<Event>) -> JoinHandle<()> {
+        let (client, host, events) = (client.clone(), host.to_string(), events.clone());
+
+        tokio::spawn(async move {
+            // sending only fails once the event loop is gone
+            let progress = |done, total| { let _ = events.send(Event::IndexProgress(done, total)); };
+            let index = LibraryIndex::build(&client, &host, &progress).await;
+            let _ = events.send(Event::LibraryIndexed(index));
+        })
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.loaded
+    }
+
+    // in the order mpd listed them
+    pub fn songs(&self) -> impl Iterator<Item = &Song> {
+        self.entries.iter().map(|entry| &entry.song)
+    }
+
+    // songs matching every term, best matches first
+    pub fn search(&self, terms: &[Term]) -> Vec<Song> {
+        let terms: Vec<Term> = terms.iter()
+            .map(|term| Term { value: term.value.to_lowercase(), ..term.clone() })
+            .collect();
+
+        let mut matches: Vec<(i64, &Entry)> = self.entries
+            .iter()
+            .filter_map(|entry| {
+                let mut total = 0;
+                for term in &terms {
+                    let fields = entry.fields(&term.tag);
+
+                    // excluding is exact, a fuzzy match would hide too much
+                    if term.negate {
+                        if fields.iter().any(|(field, _)| field.contains(&term.value)) {
+                            return None;
+                        }
+
+                        continue;
+                    }
+
+                    total += fields.iter()
+                        .filter_map(|(field, weight)| Some(score(&term.value, field)? * weight))
+                        .max()?;
+                }
+
+                Some((total, entry))
+            })
+            .collect();
+
+        // stable, so equally good matches stay in library order
+        matches.sort_by(|(a, _), (b, _)| b.cmp(a));
+        matches.into_iter()
+            .take(MAX_RESULTS)
+            .map(|(_, entry)| entry.song.clone())
+            .collect()
+    }
+}
+
+// how well `pattern` matches `text`, both already case folded: a substring
+// beats the pattern's letters appearing in order, which beats a word
+// spelled with a typo or two
+fn score(pattern: &str, text: &str) -> Option<i64> {
+    if pattern.is_empty() || text.is_empty() {
+        return None;
+    }
+
+    if let Some(i) = text.find(pattern) {
+        let bonus = match text[..i].chars().last() {
+            None => 200,
+            Some(c) if !c.is_alphanumeric() => 100,
+            _ => 0,
+        };
+
+        // an exact match ranks above a longer text containing it
+        let extra = (text.len() - pattern.len()).min(100) as i64;
+        return Some(1000 + bonus - extra);
+    }
+
+    if let Some(score) = subsequence(pattern, text) {
+        return Some(score);
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let allowed = match pattern.len() {
+        0..=3 => return None,
+        4..=7 => 1,
+        _ => 2,
+    };
+
+    // compares against whole words as well as their beginnings, so a word
+    // still being typed can have a typo too
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .filter_map(|word| {
+            let word: Vec<char> = word.chars().collect();
+            let prefix = &word[..word.len().min(pattern.len())];
+            let distance = distance(&pattern, &word, allowed).min(distance(&pattern, prefix, allowed));
+            (distance <= allowed).then(|| 300 - 100 * distance as i64)
+        })
+        .max()
+}
+
+// every letter of the pattern in order, scoring runs of consecutive letters
+// and letters starting a word higher, and letters too far apart not at all
+fn subsequence(pattern: &str, text: &str) -> Option<i64> {
+    let mut score = 500;
+    let mut pattern = pattern.chars().peekable();
+    let mut previous: Option<char> = None;
+    let mut matched_previous = false;
+    let mut started = false;
+
+    for c in text.chars() {
+        match pattern.peek() {
+            Some(&p) if p == c => {
+                pattern.next();
+                if matched_previous {
+                    score += 10;
+                }
+
+                if previous.is_none_or(|previous| !previous.is_alphanumeric()) {
+                    score += 20;
+                }
+
+                matched_previous = true;
+                started = true;
+            },
+
+            Some(_) => {
+                if started {
+                    score -= 2;
+                }
+
+                matched_previous = false;
+            },
+
+            None => break,
+        }
+
+        previous = Some(c);
+    }
+
+    // below a substring match however well it does, and above a typo
+    (pattern.peek().is_none() && score >= 400).then(|| score.min(900))
+}
+
+// damerau-levenshtein, counting a swap of neighbouring letters as one edit,
+// giving up with anything over `limit` once it can only get worse
+fn distance(a: &[char], b: &[char], limit: usize) -> usize {
+    // only the two rows before the current one are ever looked back on
+    let mut before = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+
+        // every later row builds on one of the last two
+        let over = |row: &[usize]| row.iter().all(|&d| d > limit);
+        if over(&current) && over(&previous) {
+            return limit + 1;
+        }
+
+        // the oldest row is written over as the next one
+        std::mem::swap(&mut before, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+
+    fn index(songs: &[(&str, &str)]) -> LibraryIndex {
+        let mut listing = String::new();
+        for (i, (title, album)) in songs.iter().enumerate() {
+            let _ = write!(listing, "file: {}.flac\nTitle: {}\nAlbum: {}\n", i, title, album);
+        }
+
+        listing.push_str("OK\n");
+        let songs = cache::parse(listing.as_bytes()).unwrap();
+        LibraryIndex { entries: songs.into_iter().map(Entry::new).collect(), loaded: true }
+    }
+
+    fn term(tag: Option<Tag>, value: &str, negate: bool) -> Term {
+        Term { tag, value: value.to_string(), negate }
+    }
+
+    fn titles(index: &LibraryIndex, terms: &[Term]) -> Vec<String> {
+        index.search(terms).iter().filter_map(|song| song.title()).map(str::to_string).collect()
+    }
+
+    #[test]
+    fn substring_beats_subsequence() {
+        let index = index(&[("Hold Moon", ""), ("Homogenic", "")]);
+        assert_eq!(titles(&index, &[term(None, "homo", false)]), ["Homogenic", "Hold Moon"]);
+    }
+
+    #[test]
+    fn subsequence_beats_typo() {
+        let index = index(&[("Hmogenic", ""), ("Home Of Genic", ""), ("Unrelated", "")]);
+        assert_eq!(titles(&index, &[term(None, "homogenic", false)]), ["Home Of Genic", "Hmogenic"]);
+        assert_eq!(distance(&['a', 'b', 'c', 'd'], &['a', 'c', 'b', 'd'], 2), 1);
+        assert_eq!(distance(&['a', 'b', 'c', 'd'], &['w', 'x', 'y', 'z'], 1), 2);
+    }
+
+    #[test]
+    fn negated_terms_exclude() {
+        let index = index(&[("Joga", "Homogenic"), ("Joga (live)", "Live")]);
+        let terms = [term(None, "joga", false), term(None, "live", true)];
+        assert_eq!(titles(&index, &terms), ["Joga"]);
+    }
+
+    #[test]
+    fn scoped_terms() {
+        let index = index(&[("Homogenic", "Other"), ("Joga", "Homogenic")]);
+        assert_eq!(titles(&index, &[term(Some(Tag::Album), "homogenic", false)]), ["Joga"]);
+        assert_eq!(titles(&index, &[term(Some(Tag::Title), "homogenic", false)]), ["Homogenic"]);
+    }
+
+    #[test]
+    fn results_are_capped() {
+        let songs: Vec<(String, &str)> = (0..MAX_RESULTS + 10).map(|i| (format!("Song {}", i), "")).collect();
+        let songs: Vec<(&str, &str)> = songs.iter().map(|(title, album)| (title.as_str(), *album)).collect();
+        assert_eq!(index(&songs).search(&[term(None, "song", false)]).len(), MAX_RESULTS);
+    }
+}
@@ -1,83 +1,547 @@
-use crate::block::Playlists;//, MainBlock, Library, Queue, SelectableList, Playlists, Search, Sort, Playbar };
-use crate::chunk::{ Chunks, BlockKind };
-use crate::event::Key;
-use crate::theme::Theme;
-use crate::client::StrofaClient;
-use crate::state::State;
+use crate::chunk::BlockKind;
+use crate::command::Command;
+use crate::event::{ Key, KeyCode, Modifiers, key };
 
-use tui::backend::Backend;
-use anyhow::Result;
-use std::collections::{ VecDeque, HashMap };
-use tui::layout::Rect;
-use mpd_client::Client;
+use serde::{ Deserialize, Deserializer };
+use std::collections::HashMap;
+use std::time::{ Duration, Instant };
 
+// how long a sequence waits for its next key before what was typed so far is acted on
+const TIMEOUT: Duration = Duration::from_millis(1000);
+
+// like vim's, a count is only ever meant to be big enough
+const MAX_COUNT: usize = 99999;
+
+// the keys of a binding, eg. `gg`, `<C-w>j` or just `q`
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub struct KeySequence(pub Vec<Key>);
+
+impl std::fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for key in &self.0 {
+            write!(f, "{}", key)?;
+        }
+
+        Ok(())
+    }
+}
+
+// keys one after the other, each a character or a key in angle brackets
+impl std::str::FromStr for KeySequence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keys = Vec::new();
+        let mut rest = s;
+
+        while let Some(c) = rest.chars().next() {
+            // a `<` not starting a key in brackets is the key itself
+            let bracketed = match c {
+                '<' => rest[1..].find('>').map(|end| &rest[..end + 2]).filter(|key| key.len() > 2 && key.parse::<Key>().is_ok()),
+                _ => None,
+            };
+
+            let key = bracketed.unwrap_or(&rest[..c.len_utf8()]);
+            keys.push(key.parse()?);
+            rest = &rest[key.len()..];
+        }
+
+        match keys.is_empty() {
+            true => Err(String::from("empty key sequence")),
+            false => Ok(KeySequence(keys)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for KeySequence {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// a set of bindings in effect in some part of the ui; where several are,
+// the more specific ones hide the keys they bind from those after them
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Keymap {
+    Global,
+    // moving between blocks while none is active
+    Hover,
+    Block(BlockKind),
+    // the kind of view shown in the centre
+    Queue,
+    SearchResults,
+    Artists,
+    Albums,
+    Tracks,
+    // popups, which take no other bindings while open
+    Confirm,
+    PlaylistPicker,
+    Help,
+    CommandLine,
+}
+
+impl std::fmt::Display for Keymap {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Keymap::Global => write!(f, "global"),
+            Keymap::Hover => write!(f, "hover"),
+            Keymap::Block(blk) => write!(f, "{}", blk),
+            Keymap::Queue => write!(f, "queue"),
+            Keymap::SearchResults => write!(f, "search_results"),
+            Keymap::Artists => write!(f, "artists"),
+            Keymap::Albums => write!(f, "albums"),
+            Keymap::Tracks => write!(f, "tracks"),
+            Keymap::Confirm => write!(f, "confirm"),
+            Keymap::PlaylistPicker => write!(f, "playlist_picker"),
+            Keymap::Help => write!(f, "help"),
+            Keymap::CommandLine => write!(f, "command_line"),
+        }
+    }
+}
+
+impl std::str::FromStr for Keymap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "global" => Ok(Keymap::Global),
+            "hover" => Ok(Keymap::Hover),
+            "queue" => Ok(Keymap::Queue),
+            "search_results" => Ok(Keymap::SearchResults),
+            "artists" => Ok(Keymap::Artists),
+            "albums" => Ok(Keymap::Albums),
+            "tracks" => Ok(Keymap::Tracks),
+            "confirm" => Ok(Keymap::Confirm),
+            "playlist_picker" => Ok(Keymap::PlaylistPicker),
+            "help" => Ok(Keymap::Help),
+            "command_line" => Ok(Keymap::CommandLine),
+            _ => s.parse().map(Keymap::Block).map_err(|_| format!("unknown keymap `{}`", s)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Keymap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// what typing a key comes down to, in order
+#[derive(Clone, PartialEq, Debug)]
+pub enum Action {
+    // along with the count typed before it, if any
+    Run(Command, Option<usize>),
+    // bound to nothing, which is only of use as text typed
+    Unbound(Key),
+}
+
+// a trie of key sequences, where a sequence can both run a command and lead
+// on to longer ones, like `g` and `gg`
+#[derive(Default)]
+struct Node {
+    command: Option<Command>,
+    next: HashMap<Key, Node>,
+}
+
+impl Node {
+    fn get(&self, keys: &[Key]) -> Option<&Node> {
+        match keys.split_first() {
+            None => Some(self),
+            Some((key, rest)) => self.next.get(key)?.get(rest),
+        }
+    }
+
+    // telling whether the node is left without a command or anything after it
+    fn unbind(&mut self, keys: &[Key]) -> bool {
+        match keys.split_first() {
+            None => self.command = None,
+            Some((key, rest)) => {
+                if self.next.get_mut(key).is_some_and(|node| node.unbind(rest)) {
+                    self.next.remove(key);
+                }
+            },
+        }
+
+        self.command.is_none() && self.next.is_empty()
+    }
+
+    fn bindings(&self, keymap: Keymap, keys: &mut Vec<Key>, bindings: &mut Vec<(Keymap, KeySequence, Command)>) {
+        if let Some(command) = &self.command {
+            bindings.push((keymap, KeySequence(keys.clone()), command.clone()));
+        }
+
+        for (key, node) in &self.next {
+            keys.push(*key);
+            node.bindings(keymap, keys, bindings);
+            keys.pop();
+        }
+    }
+}
 
 //maybe some gentlemens rule where Ctrl(X) = in the current active window, Char(X) = in the whole app
 //or the other way 'round
-pub struct KeyBindings(pub HashMap<Key, String>);
+pub struct KeyBindings {
+    keymaps: HashMap<Keymap, Node>,
+    // the keys typed so far of a sequence not finished yet, and the count typed before them
+    pending: Vec<Key>,
+    count: Option<usize>,
+    since: Instant,
+}
+
 impl Default for KeyBindings {
     fn default() -> Self {
-        let mut map: HashMap<Key, String> = HashMap::new();
-
-        map.insert(Key::Backspace, "back".to_string());
-        map.insert(Key::Char('q'), "to_queue".to_string());
-        map.insert(Key::Char('e'), "to_playlists".to_string());
-        map.insert(Key::Ctrl('b'), "toggle_top".to_string());
-
-        map.insert(Key::Char('v'), "jump_to_start".to_string());
-        map.insert(Key::Char('z'), "jump_to_end".to_string());
-        map.insert(Key::Char('f'), "jump_to_album".to_string());
-        map.insert(Key::Char('c'), "jump_to_artist".to_string());
-
-        map.insert(Key::Char('-'), "decrease_volume".to_string());
-        map.insert(Key::Char('+'), "increase_volume".to_string());
-        // map.insert(Key::Shift('-'), "decrease_volume_big".to_string());
-        // map.insert(Key::Shift('+'), "increase_volume_big".to_string());
-
-        map.insert(Key::Char(' '), "toggle_playback".to_string());
-        map.insert(Key::Char('<'), "seek_backwards".to_string());
-        map.insert(Key::Char('>'), "seek_forwards".to_string());
-        map.insert(Key::Char(']'), "next_track".to_string());
-        map.insert(Key::Char('['), "previous_track".to_string());
-        map.insert(Key::Char('s'), "shuffle".to_string());
-        map.insert(Key::Char('r'), "repeat".to_string());
-        map.insert(Key::Char('/'), "search".to_string());
-        // map.insert(Key::Enter, "submit".to_string());
+        let mut map = Self { keymaps: HashMap::new(), pending: Vec::new(), count: None, since: Instant::now() };
+
+        use Keymap::*;
+        map.bind(Global, &[key!(Backspace)], Command::Back);
+        map.bind(Global, &[key!(Ctrl + 'f')], Command::Forward);
+        map.bind(Global, &[key!('q')], Command::ToQueue);
+        map.bind(Global, &[key!('e')], Command::ToPlaylists);
+        map.bind(Global, &[key!(Ctrl + 'b')], Command::ToggleTop);
+
+        // what these mean is up to the active block
+        map.bind(Global, &[key!(Up)], Command::MoveUp);
+        map.bind(Global, &[key!(Down)], Command::MoveDown);
+        map.bind(Global, &[key!(Left)], Command::MoveLeft);
+        map.bind(Global, &[key!(Right)], Command::MoveRight);
+        map.bind(Global, &[key!(Tab)], Command::NextPane);
+        map.bind(Global, &[key!(Enter)], Command::Select);
+        map.bind(Global, &[key!(Esc)], Command::Cancel);
+
+        map.bind(Global, &[key!('j')], Command::MoveDown);
+        map.bind(Global, &[key!('k')], Command::MoveUp);
+        map.bind(Global, &[key!('g'), key!('g')], Command::JumpToStart);
+        map.bind(Global, &[key!('G')], Command::JumpToEnd);
+        map.bind(Global, &[key!(Home)], Command::JumpToStart);
+        map.bind(Global, &[key!(End)], Command::JumpToEnd);
+        map.bind(Global, &[key!(PageUp)], Command::PageUp);
+        map.bind(Global, &[key!(PageDown)], Command::PageDown);
+        map.bind(Global, &[key!(Ctrl + 'u')], Command::PageUp);
+        map.bind(Global, &[key!(Ctrl + 'd')], Command::PageDown);
+
+        map.bind(Global, &[key!('v')], Command::JumpToStart);
+        map.bind(Global, &[key!('z')], Command::JumpToEnd);
+        map.bind(Global, &[key!('f')], Command::JumpToAlbum);
+        map.bind(Global, &[key!('c')], Command::JumpToArtist);
+
+        map.bind(Global, &[key!('-')], Command::SetVolume(-5));
+        map.bind(Global, &[key!('+')], Command::SetVolume(5));
+        // shift only changes the character typed, so the bigger steps are on alt
+        map.bind(Global, &[key!(Alt + '-')], Command::SetVolume(-10));
+        map.bind(Global, &[key!(Alt + '+')], Command::SetVolume(10));
+
+        map.bind(Global, &[key!(' ')], Command::TogglePlayback);
+        map.bind(Global, &[key!('<')], Command::Seek(-10));
+        map.bind(Global, &[key!('>')], Command::Seek(10));
+        map.bind(Global, &[key!(']')], Command::NextTrack);
+        map.bind(Global, &[key!('[')], Command::PreviousTrack);
+        map.bind(Global, &[key!('s')], Command::Shuffle);
+        map.bind(Global, &[key!('r')], Command::Repeat);
+        map.bind(Global, &[key!('/')], Command::Search(None));
+        map.bind(Global, &[key!(':')], Command::CommandLine);
+        map.bind(Global, &[key!('?')], Command::Help);
 
         // map.insert("copy_song_name".to_string(), Key::Char('c'));
         // map.insert("copy_album_name".to_string(), Key::Char('C'));
-        map.insert(Key::Char('x'), "add_item_to_queue".to_string());
+        map.bind(Global, &[key!('x')], Command::AddItemToQueue);
+        map.bind(Global, &[key!('P')], Command::PlayItem);
+
+        map.bind(Hover, &[key!(Up)], Command::HoverUp);
+        map.bind(Hover, &[key!(Down)], Command::HoverDown);
+        map.bind(Hover, &[key!(Left)], Command::HoverLeft);
+        map.bind(Hover, &[key!(Right)], Command::HoverRight);
 
-        Self(map)
+        // the search takes every other key as text
+        let search = Block(BlockKind::TopLeft);
+        map.bind(search, &[key!(Enter)], Command::Select);
+        map.bind(search, &[key!(Esc)], Command::Cancel);
+        map.bind(search, &[key!(Backspace)], Command::DeleteBackward);
+        map.bind(search, &[key!(Ctrl + 'w')], Command::DeleteWord);
+        map.bind(search, &[key!(Ctrl + 'u')], Command::ClearLine);
+        map.bind(search, &[key!(Left)], Command::MoveLeft);
+        map.bind(search, &[key!(Right)], Command::MoveRight);
+        map.bind(search, &[key!(Home)], Command::JumpToStart);
+        map.bind(search, &[key!(Ctrl + 'a')], Command::JumpToStart);
+        map.bind(search, &[key!(End)], Command::JumpToEnd);
+        map.bind(search, &[key!(Ctrl + 'e')], Command::JumpToEnd);
+        map.bind(search, &[key!(Up)], Command::MoveUp);
+        map.bind(search, &[key!(Down)], Command::MoveDown);
+
+        map.bind(Block(BlockKind::TopRight), &[key!('d')], Command::DropSortKey);
+
+        map.bind(Queue, &[key!('d'), key!('d')], Command::DeleteItem);
+        map.bind(Queue, &[key!('K')], Command::MoveItemUp);
+        map.bind(Queue, &[key!('J')], Command::MoveItemDown);
+        map.bind(Queue, &[key!('p')], Command::PlayNext);
+        map.bind(Queue, &[key!('o')], Command::JumpToPlaying);
+        map.bind(Queue, &[key!('X')], Command::CropQueue);
+        map.bind(Queue, &[key!('C')], Command::AskClearQueue);
+        map.bind(Queue, &[key!('A')], Command::AddToPlaylist(None));
+
+        map.bind(Confirm, &[key!('y')], Command::Select);
+        map.bind(Confirm, &[key!(Enter)], Command::Select);
+        map.bind(Confirm, &[key!('n')], Command::Cancel);
+        map.bind(Confirm, &[key!(Esc)], Command::Cancel);
+
+        map.bind(PlaylistPicker, &[key!(Up)], Command::MoveUp);
+        map.bind(PlaylistPicker, &[key!(Down)], Command::MoveDown);
+        map.bind(PlaylistPicker, &[key!(PageUp)], Command::PageUp);
+        map.bind(PlaylistPicker, &[key!(PageDown)], Command::PageDown);
+        map.bind(PlaylistPicker, &[key!(Home)], Command::JumpToStart);
+        map.bind(PlaylistPicker, &[key!(End)], Command::JumpToEnd);
+        map.bind(PlaylistPicker, &[key!(Enter)], Command::Select);
+        map.bind(PlaylistPicker, &[key!(Esc)], Command::Cancel);
+
+        // typing filters the bindings listed
+        map.bind(Help, &[key!(Up)], Command::MoveUp);
+        map.bind(Help, &[key!(Down)], Command::MoveDown);
+        map.bind(Help, &[key!(PageUp)], Command::PageUp);
+        map.bind(Help, &[key!(PageDown)], Command::PageDown);
+        map.bind(Help, &[key!(Home)], Command::JumpToStart);
+        map.bind(Help, &[key!(End)], Command::JumpToEnd);
+        map.bind(Help, &[key!(Backspace)], Command::DeleteBackward);
+        map.bind(Help, &[key!(Ctrl + 'u')], Command::ClearLine);
+        map.bind(Help, &[key!(Enter)], Command::Cancel);
+        map.bind(Help, &[key!(Esc)], Command::Cancel);
+
+        // as does the command line
+        map.bind(CommandLine, &[key!(Enter)], Command::Select);
+        map.bind(CommandLine, &[key!(Esc)], Command::Cancel);
+        map.bind(CommandLine, &[key!(Tab)], Command::Complete);
+        map.bind(CommandLine, &[key!(Backspace)], Command::DeleteBackward);
+        map.bind(CommandLine, &[key!(Ctrl + 'w')], Command::DeleteWord);
+        map.bind(CommandLine, &[key!(Ctrl + 'u')], Command::ClearLine);
+        map.bind(CommandLine, &[key!(Up)], Command::MoveUp);
+        map.bind(CommandLine, &[key!(Down)], Command::MoveDown);
+
+        map
     }
 }
 
 impl KeyBindings {
-    pub async fn event(state: &mut State, cmd: &str) -> Result<()> {
-        match cmd {
-            // binds manipulating ui
-            // "to_queue" => state.blocks.set_main(MainBlock::Queue(Queue::new(&state.client).await?)),
-            // "toggle_top" => self.blocks
-            // "to_playlists" => state.chunks.set_active(BlockKind::Playlists),
-            // "search" => state.chunks.set_active(BlockKind::Search),
-          
-            // binds manipulating mpd  
-            "toggle_playback" => state.client.toggle_playback().await?,
-            "decrease_volume" => state.client.set_volume(-5).await?,
-            "increase_volume" => state.client.set_volume(5).await?,
-            "decrease_volume_big" => state.client.set_volume(-10).await?,
-            "increase_volume_big" => state.client.set_volume(10).await?,
-            "next_track" => state.client.next_track().await?,
-            "previous_track" => state.client.previous_track().await?,
-            "seek_forwards" => state.client.seek_forwards(10).await?,
-            "seek_backwards" => state.client.seek_backwards(10).await?,
-            "shuffle" => state.client.toggle_shuffle().await?,
-            "repeat" => state.client.toggle_repeat().await?,
-            // "jump_to_start" => self.blocks.playbar.jump_to_start(self.client.clone()).await,
-
-            _ => {},
-        }
-
-        Ok(()) 
-    }
-}
\ No newline at end of file
+    pub fn bind(&mut self, keymap: Keymap, keys: &[Key], command: Command) {
+        let mut node = self.keymaps.entry(keymap).or_default();
+        for key in keys {
+            node = node.next.entry(*key).or_default();
+        }
+
+        node.command = Some(command);
+    }
+
+    pub fn unbind(&mut self, keymap: Keymap, keys: &[Key]) {
+        if let Some(root) = self.keymaps.get_mut(&keymap) {
+            root.unbind(keys);
+        }
+    }
+
+    // user bindings replace the default for the same keys, an empty command unbinds them
+    pub fn merge(mut self, keymap: Keymap, overrides: &HashMap<KeySequence, Binding>) -> Self {
+        for (KeySequence(keys), Binding(cmd)) in overrides {
+            match cmd {
+                Some(cmd) => self.bind(keymap, keys, cmd.clone()),
+                None => self.unbind(keymap, keys),
+            }
+        }
+
+        self
+    }
+
+    // every sequence that can be typed in `keymaps`, by keymap in the same
+    // order; those hidden by more specific keymaps are left out
+    pub fn bindings(&self, keymaps: &[Keymap]) -> Vec<(Keymap, KeySequence, Command)> {
+        let mut bindings = Vec::new();
+        for keymap in keymaps {
+            if let Some(root) = self.keymaps.get(keymap) {
+                root.bindings(*keymap, &mut Vec::new(), &mut bindings);
+            }
+        }
+
+        bindings.retain(|(keymap, KeySequence(keys), _)| self.reachable(keymaps, *keymap, keys));
+        bindings.sort_by_cached_key(|(keymap, keys, _)| (keymaps.iter().position(|k| k == keymap), keys.to_string()));
+        bindings
+    }
+
+    // typing `keys` gets to `keymap`'s binding unless a more specific keymap
+    // binds the same keys, or runs a command for a part of them first
+    fn reachable(&self, keymaps: &[Keymap], keymap: Keymap, keys: &[Key]) -> bool {
+        let resolves = |prefix: &[Key]| keymaps.iter().find(|k| self.keymaps.get(k).and_then(|root| root.get(prefix)).is_some());
+        let waits = (1..keys.len()).all(|len| self.get(keymaps, &keys[..len]).is_some_and(|node| !node.next.is_empty()));
+        waits && resolves(keys) == Some(&keymap)
+    }
+
+    // the first of `keymaps` to bind anything starting with `keys`
+    fn get(&self, keymaps: &[Keymap], keys: &[Key]) -> Option<&Node> {
+        keymaps.iter().find_map(|keymap| self.keymaps.get(keymap)?.get(keys))
+    }
+
+    // the count and keys typed so far, for the ui to show
+    pub fn pending(&self) -> Option<String> {
+        let keys = KeySequence(self.pending.clone());
+        match (self.count, self.pending.is_empty()) {
+            (None, true) => None,
+            (Some(count), true) => Some(count.to_string()),
+            (None, false) => Some(keys.to_string()),
+            (Some(count), false) => Some(format!("{}{}", count, keys)),
+        }
+    }
+
+    // `keymaps` are those in effect, most specific first. digits before a
+    // sequence make up its count, unless the digit starts a binding of its
+    // own; 0 only counts after another digit, like in vim. counts go with the
+    // global bindings, so text typed and popups take digits as they are
+    pub fn feed(&mut self, key: Key, keymaps: &[Keymap]) -> Vec<Action> {
+        self.since = Instant::now();
+
+        if key == key!(Esc) && self.pending().is_some() {
+            self.pending.clear();
+            self.count = None;
+            return Vec::new();
+        }
+
+        let counts = keymaps.contains(&Keymap::Global);
+        if let (KeyCode::Char(c), Modifiers::NONE, true, true) = (key.code, key.modifiers, self.pending.is_empty(), counts) {
+            let counting = self.count.is_some() || (c != '0' && self.get(keymaps, &[key]).is_none());
+            if let Some(digit) = c.to_digit(10).filter(|_| counting) {
+                self.count = Some((self.count.unwrap_or(0) * 10 + digit as usize).min(MAX_COUNT));
+                return Vec::new();
+            }
+        }
+
+        let mut keys = self.pending.clone();
+        keys.push(key);
+
+        match self.get(keymaps, &keys).map(|node| (node.command.clone(), node.next.is_empty())) {
+            Some((Some(command), true)) => {
+                self.pending.clear();
+                vec![Action::Run(command, self.count.take())]
+            },
+
+            // waiting for the next key, or the timeout
+            Some((_, false)) => {
+                self.pending = keys;
+                Vec::new()
+            },
+
+            // the key led nowhere, so whatever was pending is acted on before
+            // starting over with it
+            _ if !self.pending.is_empty() => {
+                let mut actions = self.flush(keymaps);
+                actions.extend(self.feed(key, keymaps));
+                actions
+            },
+
+            _ => {
+                self.count = None;
+                vec![Action::Unbound(key)]
+            },
+        }
+    }
+
+    // called periodically, acts on a sequence left unfinished for too long
+    pub fn expire(&mut self, keymaps: &[Keymap]) -> Vec<Action> {
+        match !self.pending.is_empty() && self.since.elapsed() >= TIMEOUT {
+            true => self.flush(keymaps),
+            false => Vec::new(),
+        }
+    }
+
+    // runs the command of the keys pending, if they have one, or otherwise
+    // gives them back one by one
+    fn flush(&mut self, keymaps: &[Keymap]) -> Vec<Action> {
+        let keys = std::mem::take(&mut self.pending);
+        let count = self.count.take();
+
+        match self.get(keymaps, &keys).and_then(|node| node.command.clone()) {
+            Some(command) => vec![Action::Run(command, count)],
+            None => keys.into_iter().map(Action::Unbound).collect(),
+        }
+    }
+}
+
+// a command as written in the config file, where `""` stands for no command
+#[derive(Clone, Debug)]
+pub struct Binding(pub Option<Command>);
+
+impl<'de> Deserialize<'de> for Binding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "" => Ok(Binding(None)),
+            cmd => cmd.parse().map(|cmd| Binding(Some(cmd))).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLOBAL: &[Keymap] = &[Keymap::Global];
+
+    fn bindings() -> KeyBindings {
+        let mut keys = KeyBindings { keymaps: HashMap::new(), pending: Vec::new(), count: None, since: Instant::now() };
+        keys.bind(Keymap::Global, &[key!('g')], Command::JumpToEnd);
+        keys.bind(Keymap::Global, &[key!('g'), key!('g')], Command::JumpToStart);
+        keys.bind(Keymap::Global, &[key!('j')], Command::MoveDown);
+        keys
+    }
+
+    fn feed(keys: &mut KeyBindings, typed: &str) -> Vec<Action> {
+        typed.chars().flat_map(|c| keys.feed(key!(Char(c)), GLOBAL)).collect()
+    }
+
+    #[test]
+    fn longer_sequence_wins() {
+        let mut keys = bindings();
+        assert_eq!(feed(&mut keys, "gg"), vec![Action::Run(Command::JumpToStart, None)]);
+    }
+
+    #[test]
+    fn shorter_sequence_runs_before_a_key_leading_nowhere() {
+        let mut keys = bindings();
+        assert_eq!(feed(&mut keys, "g"), vec![]);
+        assert_eq!(keys.pending(), Some("g".to_string()));
+        assert_eq!(feed(&mut keys, "j"), vec![Action::Run(Command::JumpToEnd, None), Action::Run(Command::MoveDown, None)]);
+        assert_eq!(feed(&mut keys, "gx"), vec![Action::Run(Command::JumpToEnd, None), Action::Unbound(key!('x'))]);
+    }
+
+    #[test]
+    fn timeout_flushes() {
+        let mut keys = bindings();
+        assert_eq!(feed(&mut keys, "g"), vec![]);
+        assert_eq!(keys.expire(GLOBAL), vec![]);
+
+        keys.since = Instant::now() - TIMEOUT;
+        assert_eq!(keys.expire(GLOBAL), vec![Action::Run(Command::JumpToEnd, None)]);
+        assert_eq!(keys.pending(), None);
+    }
+
+    #[test]
+    fn count_then_sequence() {
+        let mut keys = bindings();
+        assert_eq!(feed(&mut keys, "12gg"), vec![Action::Run(Command::JumpToStart, Some(12))]);
+        assert_eq!(feed(&mut keys, "10j"), vec![Action::Run(Command::MoveDown, Some(10))]);
+        // and none left over for the next key
+        assert_eq!(feed(&mut keys, "j"), vec![Action::Run(Command::MoveDown, None)]);
+    }
+
+    #[test]
+    fn count_is_capped() {
+        let mut keys = bindings();
+        assert_eq!(feed(&mut keys, "99999999999999999999999j"), vec![Action::Run(Command::MoveDown, Some(MAX_COUNT))]);
+    }
+
+    #[test]
+    fn esc_clears_pending() {
+        let mut keys = bindings();
+        assert_eq!(feed(&mut keys, "5g"), vec![]);
+        assert_eq!(keys.feed(key!(Esc), GLOBAL), vec![]);
+        assert_eq!(keys.pending(), None);
+        assert_eq!(feed(&mut keys, "j"), vec![Action::Run(Command::MoveDown, None)]);
+    }
+
+    #[test]
+    fn digits_are_text_outside_global_bindings() {
+        let mut keys = bindings();
+        assert_eq!(keys.feed(key!('5'), &[Keymap::Block(BlockKind::TopLeft)]), vec![Action::Unbound(key!('5'))]);
+    }
+}
@@ -1,55 +1,112 @@
-#![feature(async_stream)]
-#![feature(if_let_guard)]
-#![feature(async_await)]
+// event handlers match on the command even when they only handle one, leaving
+// room for more
+#![allow(clippy::single_match)]
 
 mod state;
 use state::State;
 
 mod key;
-use key::KeyBindings;
+use key::Action;
 
 mod block;
 
 mod chunk;
-use chunk::{ Chunks };
+use chunk::Chunks;
+use block::{ StandardBlock, Search };
 
 mod event;
+use event::key;
 mod theme;
 mod client;
+mod config;
+mod command;
+mod history;
+mod query;
+mod index;
+mod cache;
+mod collate;
+use config::{ Args, Config, ConnectionConfig, USAGE };
 
 use tui::{ Terminal, Frame };
 use tui::backend::{ Backend, CrosstermBackend };
 use tui::layout::{ Layout, Constraint, Direction, Rect };
-use crossterm::{ ExecutableCommand, execute, cursor::MoveTo };
+use crossterm::{ ExecutableCommand, execute, event::{ EnableMouseCapture, DisableMouseCapture } };
 use crossterm::terminal::{ enable_raw_mode, disable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen, SetTitle };
 
-use futures_util::{ StreamExt, pin_mut };
 use tracing_subscriber::{ EnvFilter, FmtSubscriber };
-use tokio::net::TcpStream;
-use mpd_client::{ Client, Subsystem };
+use mpd_client::Subsystem;
 use anyhow::Result;
 
 pub const SMALL_TERMINAL_WIDTH: u16 = 150;
 pub const SMALL_TERMINAL_HEIGHT: u16 = 45;
+pub const TICK_RATE: std::time::Duration = std::time::Duration::from_millis(250);
 
 // pub type Element<B> = Box<dyn Render<B>>;
 pub trait Render<B: Backend>: Send {
     fn render(&self, f: &mut Frame<B>, state: &State, layout_chunk: Rect);
 }
 
+// the top, centre and bottom chunks
+pub fn parent_layout(size: Rect) -> Vec<Rect> {
+    let margin = if size.height > SMALL_TERMINAL_HEIGHT { 1 } else { 0 };
+    let constraints = //if size.width > SMALL_TERMINAL_WIDTH {
+        // vec![Constraint::Min(1), Constraint::Length(6)]
+    // } else {
+        vec![Constraint::Length(3), Constraint::Min(1), Constraint::Length(6)]
+        // vec![Constaint::]
+    // };
+    ;
+
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(constraints)
+        .margin(margin)
+        .split(size)
+}
+
+// what the key bindings made of the keys typed; mpd refusing a command, eg.
+// seeking while stopped, is shown rather than ending strofa
+async fn run(state: &mut State, actions: Vec<Action>) {
+    for action in actions {
+        match action {
+            Action::Run(cmd, count) => {
+                if let Err(e) = cmd.execute(state, count).await {
+                    state.error = Some(e.to_string());
+                }
+            },
+
+            Action::Unbound(key) => Chunks::input(state, key),
+        }
+    }
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     FmtSubscriber::builder()
         .with_env_filter(EnvFilter::from_default_env())
         .init();
 
-    let connection = TcpStream::connect("localhost:6600").await?;
-    let (client, mut state_changes) = Client::connect(connection).await?;
-    // pin_mut!(state_changes);
+    let args = match ConnectionConfig::from_args(std::env::args().skip(1))? {
+        Args::Connect(args) => args,
+        // before anything is drawn, so it is left on the terminal
+        Args::Help => {
+            print!("{}", USAGE);
+            return Ok(());
+        },
+    };
+
+    // connection settings in increasing order of precedence
+    let config = Config::load()?;
+    let connection = config.connection.clone()
+        .merge(ConnectionConfig::from_env()?)
+        .merge(args);
+
+    collate::init(&config.collation.articles);
+    let (client, state_changes) = client::connect(&connection).await?;
 
 
     let stdout = std::io::stdout();
-    execute!(&stdout, EnterAlternateScreen)?;
+    execute!(&stdout, EnterAlternateScreen, EnableMouseCapture)?;
     enable_raw_mode()?;
 
     let mut backend = CrosstermBackend::new(stdout);
@@ -58,9 +115,16 @@ async fn main() -> Result<()> {
     let mut terminal = Terminal::new(backend)?;
     terminal.hide_cursor()?;
 
-    let mut state = State::new(client).await?;
-    let events = event::Events::new();
-    pin_mut!(events);
+    let mut events = event::Events::new(state_changes, TICK_RATE);
+    let mut state = State::new(client, config, connection.address().to_string(), events.sender());
+    state.indexing = Some(index::LibraryIndex::rebuild(&state.client, &state.host, &state.events));
+    Chunks::load(&mut state, history::Location::new(history::View::Queue), chunk::Step::Replace);
+
+    // the playbar and playlists are fetched as if mpd had just reported them
+    // changing, once the first frame is up
+    for subsystem in [Subsystem::Player, Subsystem::StoredPlaylist] {
+        let _ = state.events.send(event::Event::StateChange(subsystem));
+    }
 
     loop {
         if let Ok(size) = terminal.backend().size() {
@@ -70,85 +134,65 @@ async fn main() -> Result<()> {
         // drawing 
         terminal.autoresize()?;
         terminal.draw(|f| {
-            let margin = if state.size.height > SMALL_TERMINAL_HEIGHT { 1 } else { 0 };
-            let constraints = //if state.size.width > SMALL_TERMINAL_WIDTH {
-                // vec![Constraint::Min(1), Constraint::Length(6)]
-            // } else {
-                vec![Constraint::Length(3), Constraint::Min(1), Constraint::Length(6)]
-                // vec![Constaint::]
-            // };
-            ;
-
-            let parent_layout = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints(constraints.as_ref())
-                .margin(margin)
-                .split(f.size());
-
+            let parent_layout = parent_layout(f.size());
 
             state.chunks.top.render(f, &state, parent_layout[0]);
             state.chunks.centre.render(f, &state, parent_layout[1]);
             state.chunks.bottom.render(f, &state, parent_layout[2]);
+
+            if let Some(popup) = &state.chunks.popup {
+                popup.render(f, &state, f.size());
+            }
         })?;
 
-        // if state.blocks.active==Some(Blokka::Search) {
-        //     terminal.show_cursor()?;
-        //     terminal.backend_mut().execute(MoveTo(
-        //       2 + state.blocks.search.cursor_position,
-        //       2,
-        //     ))?;
-        // } else {
-        //     terminal.hide_cursor()?;
-        // }
-
-        // backend events
-        if let Some(event::Event::Input(key)) = events.next().await {
-            // if state.blocks.active==Some(BlockKind::Search) {
-            //     if let event::Key::Char(_) = key {
-            //         Chunks::active_event(&mut state, key).await;
-            //         continue;
-            //     };
-            // }
-
-            match key {
-                event::Key::Ctrl('c') => break,
-
-                _ if let Some(cmd) = state.keys.0.clone().get(&key) => {
-                    KeyBindings::event(&mut state, &cmd.clone()).await?;
-                },
-
-                _ => {
-                    if let None = state.chunks.active {
-                        Chunks::hovered_event(&mut state, key).await;
-                    } else {
-                        Chunks::active_event(&mut state, key).await; 
+        match events.next().await? {
+            event::Event::Input(key) => {
+                match key {
+                    key!(Ctrl + 'c') => break,
+
+                    _ => {
+                        state.error = None;
+                        let actions = state.keys.feed(key, &state.chunks.keymaps());
+                        run(&mut state, actions).await;
                     }
                 }
-            }
-        }
+            },
 
-        // mpd events (doesn't work yet)
-        match futures::poll!(state_changes.next()) {
-            futures::task::Poll::Ready(x) => {
-                match x.transpose()? {
-                    // Some(Subsystem::Player) => state.blocks.playbar=Playbar::new(&client).await, 
-                    Some(Subsystem::Queue) => {println!("queue")},
-                    Some(Subsystem::StoredPlaylist) => {},
-                    Some(Subsystem::Update) => {}
-                    Some(Subsystem::Database) => {}
-                    _ => { continue; }
+            // popups are only answered with keys
+            event::Event::Mouse(mouse) if state.chunks.popup.is_none() => {
+                state.error = None;
+                if let Err(e) = Chunks::mouse_event(&mut state, mouse).await {
+                    state.error = Some(e.to_string());
                 }
             },
+            event::Event::Mouse(_) => {},
+
+            event::Event::StateChange(subsystem) => Chunks::idle_event(&mut state, subsystem),
+
+            event::Event::SearchResults(generation, query, found) => StandardBlock::<Search>::show_results(&mut state, generation, query, found),
+            event::Event::LibraryIndexed(index) => Chunks::library_indexed(&mut state, index),
+            event::Event::IndexProgress(done, total) => state.index_progress = Some((done, total)),
+            event::Event::ViewLoaded(generation, blk) => Chunks::view_loaded(&mut state, generation, blk),
+            event::Event::ViewProgress(generation, done, total) => Chunks::view_progress(&mut state, generation, done, total),
+            event::Event::PlaybarFetched(generation, playbar) => Chunks::playbar_fetched(&mut state, generation, playbar),
+            event::Event::PlaylistsFetched(generation, playlists) => Chunks::playlists_fetched(&mut state, generation, playlists),
+
+            // a sequence left unfinished runs what it has so far
+            event::Event::Tick => {
+                let actions = state.keys.expire(&state.chunks.keymaps());
+                run(&mut state, actions).await;
+            },
 
-            futures::task::Poll::Pending => {}
-        }    
+            // only needs a redraw, which happens at the top of the loop
+            event::Event::Resize => {},
+        }
     }
 
     // close strofa
     terminal.show_cursor()?;
     disable_raw_mode()?;
     let mut stdout = std::io::stdout();
-    execute!(stdout, LeaveAlternateScreen)?;
+    execute!(stdout, LeaveAlternateScreen, DisableMouseCapture)?;
 
     Ok(())
 }
\ No newline at end of file
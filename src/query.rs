@@ -0,0 +1,422 @@
+use mpd_client::{ filter::{ Filter, Operator }, tag::Tag };
+
+// turns what is typed into the search block into an mpd filter, either
+//   björk -album:debut genre:"trip hop"
+// where unscoped words match any tag, or mpd's own filter syntax
+//   ((Artist == "Björk") AND (!(Album == "Debut")))
+pub fn parse(input: &str) -> Result<Query, QueryError> {
+    let mut parser = Parser { chars: input.chars().collect(), pos: 0 };
+    parser.skip_whitespace();
+
+    let query = match parser.peek() {
+        None => return Err(parser.error("nothing to search for")),
+        Some('(') => Query::Filter(parser.expression()?),
+        Some(_) => Query::Terms(parser.terms()?),
+    };
+
+    parser.skip_whitespace();
+    match parser.peek() {
+        Some(c) => Err(parser.error(format!("unexpected `{}`", c))),
+        None => Ok(query),
+    }
+}
+
+pub enum Query {
+    // can be matched locally, see `LibraryIndex`
+    Terms(Vec<Term>),
+    // mpd's own syntax, which only mpd evaluates
+    Filter(Filter),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Term {
+    // `None` matches any tag
+    pub tag: Option<Tag>,
+    pub value: String,
+    pub negate: bool,
+}
+
+impl Query {
+    // for mpd to evaluate, where every term matches anywhere in its tag; sent
+    // as a search, mpd ignores case
+    pub fn filter(&self) -> Filter {
+        let terms = match self {
+            Query::Filter(filter) => return filter.clone(),
+            Query::Terms(terms) => terms,
+        };
+
+        let mut filters = terms.iter().map(|term| {
+            let tag = term.tag.clone().unwrap_or_else(Tag::any);
+            let filter = contains(tag, &term.value);
+            if term.negate { filter.negate() } else { filter }
+        });
+
+        // the parser never produces a query without terms
+        let first = filters.next().expect("query without terms");
+        filters.fold(first, Filter::and)
+    }
+
+    // for searching things that are not songs, like playlist names, by the
+    // unscoped words of the query
+    pub fn matches(&self, name: &str) -> bool {
+        let words: Vec<&str> = match self {
+            Query::Terms(terms) => terms.iter()
+                .filter(|term| term.tag.is_none() && !term.negate)
+                .map(|term| term.value.as_str())
+                .collect(),
+
+            Query::Filter(_) => Vec::new(),
+        };
+
+        let name = name.to_lowercase();
+        !words.is_empty() && words.iter().all(|word| name.contains(&word.to_lowercase()))
+    }
+}
+
+pub const PREFIXES: [&str; 8] = ["artist", "albumartist", "album", "title", "genre", "date", "composer", "file"];
+
+fn tag_named(name: &str) -> Tag {
+    match name.to_lowercase().as_str() {
+        "artist" => Tag::Artist,
+        "albumartist" => Tag::AlbumArtist,
+        "album" => Tag::Album,
+        "title" => Tag::Title,
+        "genre" => Tag::Genre,
+        "date" => Tag::Date,
+        "composer" => Tag::Composer,
+        _ => Tag::Other(name.into()),
+    }
+}
+
+// words are matched anywhere in the tag, without needing mpd to be built with
+// regular expressions
+fn contains(tag: Tag, value: &str) -> Filter {
+    Filter::new(tag, Operator::Contain, value.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryError {
+    // in characters from the start of the query
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} at column {}", self.message, self.column + 1)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        let len = s.chars().count();
+        let matches = self.chars.len() >= self.pos + len
+            && self.chars[self.pos..self.pos + len].iter().copied().eq(s.chars());
+
+        if matches {
+            self.pos += len;
+        }
+
+        matches
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> QueryError {
+        QueryError { column: self.pos, message: message.into() }
+    }
+
+    fn error_at(&self, column: usize, message: impl Into<String>) -> QueryError {
+        QueryError { column, message: message.into() }
+    }
+
+    // a quoted string, with backslash escaping the next character
+    fn string(&mut self) -> Result<String, QueryError> {
+        let start = self.pos;
+        let quote = match self.next() {
+            Some(c @ ('"' | '\'')) => c,
+            _ => return Err(self.error_at(start, "expected a quoted value")),
+        };
+
+        let mut value = String::new();
+        loop {
+            match self.next() {
+                Some('\\') => match self.next() {
+                    Some(c) => value.push(c),
+                    None => break,
+                },
+
+                Some(c) if c == quote => return Ok(value),
+                Some(c) => value.push(c),
+                None => break,
+            }
+        }
+
+        Err(self.error_at(start, "unterminated quote"))
+    }
+
+    // words separated by whitespace, all of which have to match
+    fn terms(&mut self) -> Result<Vec<Term>, QueryError> {
+        let mut terms: Vec<Term> = Vec::new();
+        // the keyword still waiting for a term, and where it was
+        let mut pending: Option<(&str, usize)> = None;
+        let mut negate = false;
+
+        loop {
+            self.skip_whitespace();
+            let start = self.pos;
+
+            if self.peek().is_none() {
+                break;
+            }
+
+            if self.eat_keyword("AND") {
+                if terms.is_empty() || pending.is_some() {
+                    return Err(self.error_at(start, "`AND` has to follow a term"));
+                }
+
+                pending = Some(("AND", start));
+                continue;
+            }
+
+            if self.eat_keyword("NOT") || self.eat("-") {
+                negate = !negate;
+                pending = Some(("NOT", start));
+                continue;
+            }
+
+            let (tag, value) = self.term()?;
+            terms.push(Term { tag, value, negate });
+            pending = None;
+            negate = false;
+        }
+
+        match pending {
+            Some((keyword, column)) => Err(self.error_at(column, format!("`{}` has to be followed by a term", keyword))),
+            None if terms.is_empty() => Err(self.error("nothing to search for")),
+            None => Ok(terms),
+        }
+    }
+
+    // a keyword only counts on its own, so that `ANDROMEDA` is still a word
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let start = self.pos;
+        if self.eat(keyword) && self.peek().is_none_or(char::is_whitespace) {
+            return true;
+        }
+
+        self.pos = start;
+        false
+    }
+
+    // `word`, `"some words"`, `tag:word` or `tag:"some words"`; a colon after
+    // anything but a tag is part of the word, as in `Mission: Impossible`
+    fn term(&mut self) -> Result<(Option<Tag>, String), QueryError> {
+        let start = self.pos;
+        let mut word = String::new();
+
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || c == '"' || c == '\'' {
+                break;
+            }
+
+            self.pos += 1;
+
+            if c == ':' && PREFIXES.contains(&word.to_lowercase().as_str()) {
+                let value = self.value()?;
+                if value.is_empty() {
+                    return Err(self.error_at(start, format!("`{}:` needs a value", word)));
+                }
+
+                return Ok((Some(tag_named(&word)), value));
+            }
+
+            word.push(c);
+        }
+
+        if word.is_empty() {
+            word = self.string()?;
+        }
+
+        Ok((None, word))
+    }
+
+    fn value(&mut self) -> Result<String, QueryError> {
+        match self.peek() {
+            Some('"' | '\'') => self.string(),
+            _ => {
+                let mut value = String::new();
+                while let Some(c) = self.peek().filter(|c| !c.is_whitespace()) {
+                    value.push(c);
+                    self.pos += 1;
+                }
+
+                Ok(value)
+            },
+        }
+    }
+
+    // `(TAG OP 'VALUE')`, `(!EXPRESSION)` or `(EXPRESSION AND EXPRESSION ...)`
+    fn expression(&mut self) -> Result<Filter, QueryError> {
+        if !self.eat("(") {
+            return Err(self.error("expected `(`"));
+        }
+
+        self.skip_whitespace();
+
+        let filter = if self.eat("!") {
+            self.skip_whitespace();
+            self.expression()?.negate()
+        } else if self.peek() == Some('(') {
+            let mut filter = self.expression()?;
+            loop {
+                self.skip_whitespace();
+                if self.peek() == Some(')') {
+                    break;
+                }
+
+                if !self.eat_keyword("AND") {
+                    return Err(self.error("expected `AND` or `)`"));
+                }
+
+                self.skip_whitespace();
+                filter = filter.and(self.expression()?);
+            }
+
+            filter
+        } else {
+            let start = self.pos;
+            let mut name = String::new();
+            while let Some(c) = self.peek().filter(|c| c.is_alphanumeric() || *c == '_' || *c == '-') {
+                name.push(c);
+                self.pos += 1;
+            }
+
+            if name.is_empty() {
+                return Err(self.error_at(start, "expected a tag"));
+            }
+
+            self.skip_whitespace();
+            let operator = self.operator()?;
+            self.skip_whitespace();
+            Filter::new(tag_named(&name), operator, self.string()?)
+        };
+
+        self.skip_whitespace();
+        if !self.eat(")") {
+            return Err(self.error("expected `)`"));
+        }
+
+        Ok(filter)
+    }
+
+    fn operator(&mut self) -> Result<Operator, QueryError> {
+        let operators = [
+            ("==", Operator::Equal),
+            ("!=", Operator::NotEqual),
+            ("=~", Operator::Match),
+            ("!~", Operator::NotMatch),
+            ("contains", Operator::Contain),
+        ];
+
+        for (text, operator) in operators {
+            if self.eat(text) {
+                return Ok(operator);
+            }
+        }
+
+        Err(self.error("expected one of `==`, `!=`, `=~`, `!~` or `contains`"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(input: &str) -> Vec<Term> {
+        match parse(input) {
+            Ok(Query::Terms(terms)) => terms,
+            Ok(Query::Filter(_)) => panic!("`{}` parsed as a filter", input),
+            Err(e) => panic!("`{}` failed: {}", input, e),
+        }
+    }
+
+    fn term(tag: Option<Tag>, value: &str, negate: bool) -> Term {
+        Term { tag, value: value.to_string(), negate }
+    }
+
+    fn error(input: &str) -> QueryError {
+        match parse(input) {
+            Err(e) => e,
+            Ok(_) => panic!("`{}` parsed", input),
+        }
+    }
+
+    #[test]
+    fn prefixes() {
+        assert_eq!(terms("björk album:debut"), vec![term(None, "björk", false), term(Some(Tag::Album), "debut", false)]);
+        assert_eq!(terms("AlbumArtist:x"), vec![term(Some(Tag::AlbumArtist), "x", false)]);
+        assert_eq!(terms("file:a/b.flac"), vec![term(Some(Tag::Other("file".into())), "a/b.flac", false)]);
+    }
+
+    #[test]
+    fn colons_in_words() {
+        assert_eq!(terms("Mission: Impossible"), vec![term(None, "Mission:", false), term(None, "Impossible", false)]);
+        assert_eq!(terms("bogus:x 12:34"), vec![term(None, "bogus:x", false), term(None, "12:34", false)]);
+    }
+
+    #[test]
+    fn negation() {
+        assert_eq!(terms("-album:debut"), vec![term(Some(Tag::Album), "debut", true)]);
+        assert_eq!(terms("a NOT b"), vec![term(None, "a", false), term(None, "b", true)]);
+        assert_eq!(terms("NOT -b"), vec![term(None, "b", false)]);
+        // only a keyword on its own
+        assert_eq!(terms("NOTHING"), vec![term(None, "NOTHING", false)]);
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(terms("genre:\"trip hop\""), vec![term(Some(Tag::Genre), "trip hop", false)]);
+        assert_eq!(terms("'a \\' b' c"), vec![term(None, "a ' b", false), term(None, "c", false)]);
+        assert_eq!(terms("a AND b"), vec![term(None, "a", false), term(None, "b", false)]);
+    }
+
+    #[test]
+    fn filters() {
+        assert!(matches!(parse("((Artist == 'Björk') AND (!(Album == 'Debut')))"), Ok(Query::Filter(_))));
+        assert!(matches!(parse("(Artist contains \"a\")"), Ok(Query::Filter(_))));
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(error("  "), QueryError { column: 2, message: "nothing to search for".into() });
+        assert_eq!(error("a \"b c").column, 2);
+        assert_eq!(error("a album:").message, "`album:` needs a value");
+        assert_eq!(error("a -").column, 2);
+        assert_eq!(error("AND a").column, 0);
+        assert_eq!(error("(Artist = 'x')").column, 8);
+        assert_eq!(error("(Artist == 'x'").column, 14);
+        assert_eq!(error("(Artist == 'x') y").message, "unexpected `y`");
+    }
+}
@@ -1,13 +1,15 @@
 use crate::chunk::Chunks;
-use crate::event::Key;
 use crate::theme::Theme;
-use crate::client::StrofaClient;
-use crate::key::KeyBindings;
+use crate::key::{ KeyBindings, Keymap };
+use crate::config::{ Config, LayoutConfig };
+use crate::event::Event;
 
-use tui::backend::Backend;
-use anyhow::Result;
 use tui::layout::Rect;
 use mpd_client::Client;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+use std::sync::Arc;
+use crate::index::LibraryIndex;
 
 pub struct State {
     pub chunks: Chunks,
@@ -15,20 +17,46 @@ pub struct State {
     pub size: Rect,
     pub theme: Theme,
     pub keys: KeyBindings,
+    pub layout: LayoutConfig,
     pub client: Client,
+    // where mpd was reached, telling apart the libraries of different servers
+    pub host: String,
+    // for tasks spawned by blocks to report back to the event loop
+    pub events: UnboundedSender<Event>,
+    // empty until first built, then replaced whenever mpd's database changes
+    pub library: Arc<LibraryIndex>,
+    pub indexing: Option<JoinHandle<()>>,
+    // songs indexed so far out of the library's, once they have been listed
+    pub index_progress: Option<(usize, usize)>,
+    // why the index couldn't be built, searches going to mpd meanwhile
+    pub index_error: Option<String>,
+    // why the last command failed, shown on the playbar
+    pub error: Option<String>,
     // pub _temp: std::marker::PhantomData<B>,
 }
 
 impl State {
-    pub async fn new(client: Client) -> Result<Self> {
-        Ok(Self {
-            chunks: Chunks::new(&client).await?,
+    // nothing is fetched yet, so the first frame is drawn without waiting on mpd
+    pub fn new(client: Client, config: Config, host: String, events: UnboundedSender<Event>) -> Self {
+        Self {
+            chunks: Chunks::new(&config.layout),
             // blocks: Blocks::new(&client).await?,
             size: Rect::default(),
-            theme: Theme::default(),
-            keys: KeyBindings::default(),
+            theme: config.theme,
+            keys: config.keymaps.iter().fold(
+                KeyBindings::default().merge(Keymap::Global, &config.keys),
+                |keys, (keymap, overrides)| keys.merge(*keymap, overrides)
+            ),
+            layout: config.layout,
             client,
+            host,
+            events,
+            library: Arc::new(LibraryIndex::default()),
+            indexing: None,
+            index_progress: None,
+            index_error: None,
+            error: None,
             // _temp: std::marker::PhantomData,
-        })
+        }
     }   
 }
\ No newline at end of file
@@ -1,6 +1,8 @@
 use tui::style::{ Color, Style };
+use serde::Deserialize;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Deserialize, Copy, Clone, Debug)]
+#[serde(default)]
 pub struct Theme {
   pub analysis_bar: Color,
   pub analysis_bar_text: Color,